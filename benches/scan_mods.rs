@@ -0,0 +1,77 @@
+//! 模组扫描的基准测试
+//! 在临时目录中生成几千个模组文件夹, 比较单线程与线程池扫描的耗时
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use StardewModsManager::mods_manager::mods_scanner::ModScanner;
+
+const NUM_MODS: usize = 3000;
+
+/// 生成一个模组库
+/// - 大部分模组在第一层, 每10个模组中有一个放在整合包文件夹里, 即第二层
+/// - 部分manifest带UTF-8 BOM, 部分使用UniqueId字段名, 与真实的模组库一致
+/// - 文件夹中附带几个非manifest文件
+fn generate_library(root: &Path) {
+    let _ = fs::remove_dir_all(root);
+    for i in 0..NUM_MODS {
+        let folder = if i.is_multiple_of(10) {
+            root.join(format!("Pack{}", i / 100))
+                .join(format!("Mod{}", i))
+        } else {
+            root.join(format!("Mod{}", i))
+        };
+        fs::create_dir_all(folder.join("assets")).unwrap();
+
+        let id_field = if i.is_multiple_of(2) {
+            "UniqueID"
+        } else {
+            "UniqueId"
+        };
+        let manifest = format!(
+            r#"{{
+    "Name": "Synthetic Mod {i}",
+    "Author": "bench",
+    "Version": "1.{i}.0",
+    "Description": "Generated for the scan benchmark.",
+    "{id_field}": "bench.mod{i}",
+    "EntryDll": "Mod{i}.dll",
+    "UpdateKeys": []
+}}"#
+        );
+        let mut bytes = Vec::new();
+        if i.is_multiple_of(3) {
+            bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+        }
+        bytes.extend_from_slice(manifest.as_bytes());
+        fs::write(folder.join("manifest.json"), bytes).unwrap();
+        fs::write(folder.join("config.json"), "{}").unwrap();
+        fs::write(folder.join("assets").join("content.json"), "{}").unwrap();
+    }
+}
+
+fn bench_scan_mods(c: &mut Criterion) {
+    let root: PathBuf = std::env::temp_dir().join("smm_bench_scan_mods");
+    generate_library(&root);
+    let scanner = ModScanner::from(root.to_str().unwrap());
+    assert_eq!(scanner.scan_mods().0.len(), NUM_MODS);
+
+    let single_thread = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("scan_mods");
+    group.sample_size(20);
+    group.bench_function("single_thread", |b| {
+        b.iter(|| single_thread.install(|| scanner.scan_mods()))
+    });
+    group.bench_function("thread_pool", |b| b.iter(|| scanner.scan_mods()));
+    group.finish();
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, bench_scan_mods);
+criterion_main!(benches);
@@ -1,22 +1,148 @@
+use crate::error::{ManagerError, Result};
+use crate::install_detect;
+use crate::link_manager::LEGACY_PROFILES_ROOT;
+use crate::mods_manager::compatibility::InstalledVersions;
+use crate::save_backup::RetentionPolicy;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// 没有添加过其他安装时, 顶层路径所属的安装名
+/// - 与数据库迁移中profiles.installation的默认值一致
+pub const DEFAULT_INSTALLATION: &str = "默认";
+
+/// 一处游戏安装, 如分别安装的正式版和测试版
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Installation {
+    pub name: String,
+    pub game_path: String,
+    pub smapi_path: String,
+    pub mods_folder_path: String,
+    /// 该安装的配置目录, 每个配置是其中的一个文件夹
+    pub profiles_root: String,
+    /// 链接到游戏Mods文件夹中的配置, 不带`--mods-path`启动SMAPI时加载
+    /// - 只能在模组库不在游戏的Mods文件夹中时设置
+    #[serde(default)]
+    pub default_profile: Option<String>,
+}
+
+impl Installation {
+    /// 按游戏文件夹的默认布局创建: SMAPI和Mods在游戏文件夹中, 配置在`Profiles`中
+    /// # 参数
+    /// - `name`: 安装名
+    /// - `game_path`: 游戏文件夹
+    pub fn from_game_path(name: &str, game_path: &Path) -> Self {
+        let path = |p: PathBuf| p.to_string_lossy().to_string();
+        Installation {
+            name: name.to_string(),
+            game_path: path(game_path.to_path_buf()),
+            smapi_path: path(game_path.join(install_detect::SMAPI_EXECUTABLE)),
+            mods_folder_path: path(game_path.join("Mods")),
+            profiles_root: path(game_path.join("Profiles")),
+            default_profile: None,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub mods_folder_path: String,
     pub smapi_path: String,
+    /// 是否在后台监视mods目录并自动刷新模组列表
+    #[serde(default)]
+    pub watch_mods_folder: bool,
+    /// 星露谷的存档目录, 未设置时使用系统的默认位置
+    #[serde(default)]
+    pub saves_path: Option<String>,
+    /// 启动前自动备份存档时的保留策略
+    #[serde(default)]
+    pub backup_retention: RetentionPolicy,
+    /// 上次读取到的SMAPI和游戏版本
+    #[serde(default)]
+    pub installed_versions: InstalledVersions,
+    /// 所有安装, 为空时只有由顶层路径组成的默认安装
+    #[serde(default)]
+    pub installations: Vec<Installation>,
+    /// 正在使用的安装名, 顶层的路径与它保持一致
+    #[serde(default)]
+    pub active_installation: Option<String>,
+    /// 配置目录, 未设置时沿用旧版本固定的位置
+    #[serde(default)]
+    pub profiles_root: Option<String>,
 }
 
 impl AppConfig {
-    pub fn load_from_file(path: &PathBuf) -> Option<Self> {
-        let content = std::fs::read_to_string(path).ok()?;
-        toml::from_str(&content).ok()
+    pub fn load_from_file(path: &PathBuf) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| ManagerError::Config(format!("无法解析{:?}: {}", path, e)))
+    }
+
+    /// 所有安装, 旧版本的配置文件只有顶层路径组成的默认安装
+    pub fn installations(&self) -> Vec<Installation> {
+        if !self.installations.is_empty() {
+            return self.installations.clone();
+        }
+        let smapi_path = Path::new(&self.smapi_path);
+        vec![Installation {
+            name: DEFAULT_INSTALLATION.to_string(),
+            game_path: smapi_path
+                .parent()
+                .unwrap_or(Path::new(""))
+                .to_string_lossy()
+                .to_string(),
+            smapi_path: self.smapi_path.clone(),
+            mods_folder_path: self.mods_folder_path.clone(),
+            profiles_root: self
+                .profiles_root
+                .clone()
+                .unwrap_or_else(|| LEGACY_PROFILES_ROOT.to_string()),
+            default_profile: None,
+        }]
+    }
+
+    /// 正在使用的安装, 找不到时为第一个
+    pub fn active_installation(&self) -> Installation {
+        let installations = self.installations();
+        installations
+            .iter()
+            .find(|i| Some(&i.name) == self.active_installation.as_ref())
+            .unwrap_or(&installations[0])
+            .clone()
+    }
+
+    /// 保存所有安装, 并把顶层路径改为正在使用的安装
+    /// # 参数
+    /// - `installations`: 所有安装
+    /// - `active`: 正在使用的安装
+    pub fn set_installations(&mut self, installations: Vec<Installation>, active: &Installation) {
+        self.mods_folder_path = active.mods_folder_path.clone();
+        self.smapi_path = active.smapi_path.clone();
+        self.profiles_root = Some(active.profiles_root.clone());
+        self.active_installation = Some(active.name.clone());
+        self.installations = installations;
+    }
+
+    /// 检查模组文件夹和SMAPI路径
+    /// # 返回值
+    /// 有问题时返回包含所有问题的`ManagerError::Config`
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        if !Path::new(&self.mods_folder_path).is_dir() {
+            problems.push(format!("模组文件夹{:?}不存在", self.mods_folder_path));
+        }
+        if !install_detect::is_smapi_launcher(Path::new(&self.smapi_path)) {
+            problems.push(format!("{:?}不是SMAPI启动器", self.smapi_path));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ManagerError::Config(problems.join("; ")))
+        }
     }
 
-    pub fn save_to_file(&self, path: &PathBuf) -> std::io::Result<()> {
-        let toml_str =
-            toml::to_string(self).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        std::fs::write(path, toml_str)
+    pub fn save_to_file(&self, path: &PathBuf) -> Result<()> {
+        let toml_str = toml::to_string(self).map_err(|e| ManagerError::Config(e.to_string()))?;
+        std::fs::write(path, toml_str)?;
+        Ok(())
     }
 }
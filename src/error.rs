@@ -0,0 +1,67 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// 整个crate统一的错误类型, `Manager`的公开方法都返回它
+#[derive(Debug)]
+pub enum ManagerError {
+    /// 数据库读写失败
+    Db(rusqlite::Error),
+    /// 文件系统读写失败
+    Io(std::io::Error),
+    /// manifest.json无法读取或解析
+    Manifest { path: PathBuf, reason: String },
+    /// 创建或删除目录链接失败
+    Link {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// 配置文件或用户输入的设置有误
+    Config(String),
+}
+
+pub type Result<T> = std::result::Result<T, ManagerError>;
+
+impl fmt::Display for ManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManagerError::Db(e) => write!(f, "数据库错误: {}", e),
+            ManagerError::Io(e) => write!(f, "文件读写错误: {}", e),
+            ManagerError::Manifest { path, reason } => {
+                write!(f, "无法解析manifest {:?}: {}", path, reason)
+            }
+            ManagerError::Link { path, source } => {
+                write!(f, "链接操作失败 {:?}: {}", path, source)
+            }
+            ManagerError::Config(msg) => write!(f, "设置错误: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ManagerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ManagerError::Db(e) => Some(e),
+            ManagerError::Io(e) => Some(e),
+            ManagerError::Link { source, .. } => Some(source),
+            ManagerError::Manifest { .. } | ManagerError::Config(_) => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ManagerError {
+    fn from(e: rusqlite::Error) -> Self {
+        ManagerError::Db(e)
+    }
+}
+
+impl From<std::io::Error> for ManagerError {
+    fn from(e: std::io::Error) -> Self {
+        ManagerError::Io(e)
+    }
+}
+
+impl From<notify::Error> for ManagerError {
+    fn from(e: notify::Error) -> Self {
+        ManagerError::Io(std::io::Error::other(e))
+    }
+}
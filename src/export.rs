@@ -0,0 +1,216 @@
+use crate::mods_manager::ModInfo;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// 导出的配置, 序列化为json
+#[derive(Debug, Serialize)]
+pub struct ProfileExport {
+    pub name: String,
+    pub description: String,
+    pub mods: Vec<ExportedMod>,
+}
+
+/// 导出配置中的一个模组
+#[derive(Debug, Serialize)]
+pub struct ExportedMod {
+    pub unique_id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+}
+
+/// 依赖图中边的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    Required,
+    Optional,
+    /// 内容包指向它的框架模组
+    ContentPackFor,
+}
+
+/// 依赖图中的一个模组
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    pub unique_id: String,
+    /// 缺失的模组没有名称和版本
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// 被依赖但不在导出范围内(整个模组库或某个配置)
+    pub missing: bool,
+}
+
+/// 依赖图中的一条边, 从依赖者指向被依赖者
+#[derive(Debug, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+/// 导出的依赖图, 可以序列化为json或Graphviz DOT
+#[derive(Debug, Serialize)]
+pub struct DependencyGraphExport {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl DependencyGraphExport {
+    /// 由一组模组建立依赖图
+    /// - 依赖中不在`mods`里的模组作为缺失的节点
+    /// - UniqueId不区分大小写, 边指向已有节点时使用节点的UniqueId
+    /// # 参数
+    /// - `mods`: 导出范围内的模组, 如整个模组库或某个配置中的模组
+    pub fn build(mods: &[ModInfo]) -> Self {
+        let mut nodes: Vec<GraphNode> = mods
+            .iter()
+            .map(|m| GraphNode {
+                unique_id: m.manifest_info.UniqueId.clone(),
+                name: Some(m.manifest_info.Name.clone()),
+                version: Some(m.manifest_info.Version.clone()),
+                missing: false,
+            })
+            .collect();
+        let mut known: HashMap<String, String> = nodes
+            .iter()
+            .map(|n| (n.unique_id.to_lowercase(), n.unique_id.clone()))
+            .collect();
+
+        let mut edges = Vec::new();
+        for m in mods {
+            let manifest = &m.manifest_info;
+            let targets = manifest
+                .ContentPackFor
+                .iter()
+                .map(|c| (&c.UniqueId, EdgeKind::ContentPackFor))
+                .chain(manifest.Dependencies.iter().map(|d| {
+                    let kind = if d.IsRequired {
+                        EdgeKind::Required
+                    } else {
+                        EdgeKind::Optional
+                    };
+                    (&d.UniqueId, kind)
+                }));
+            for (target, kind) in targets {
+                let to = known
+                    .entry(target.to_lowercase())
+                    .or_insert_with(|| {
+                        nodes.push(GraphNode {
+                            unique_id: target.clone(),
+                            name: None,
+                            version: None,
+                            missing: true,
+                        });
+                        target.clone()
+                    })
+                    .clone();
+                edges.push(GraphEdge {
+                    from: manifest.UniqueId.clone(),
+                    to,
+                    kind,
+                });
+            }
+        }
+        DependencyGraphExport { nodes, edges }
+    }
+
+    /// 转换为Graphviz DOT
+    /// - 缺失的节点为红色虚线框, 可选依赖为虚线, 内容包的边为蓝色
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph mods {\n    rankdir=LR;\n    node [shape=box];\n");
+        for node in &self.nodes {
+            let label = match (&node.name, &node.version) {
+                (Some(name), Some(version)) => format!("{}\\n{}", name, version),
+                _ => format!("{}\\n(缺失)", node.unique_id),
+            };
+            let style = if node.missing {
+                ", style=dashed, color=red"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    {} [label={}{}];\n",
+                dot_quote(&node.unique_id),
+                dot_quote(&label),
+                style
+            ));
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Required => "",
+                EdgeKind::Optional => " [style=dashed]",
+                EdgeKind::ContentPackFor => " [color=blue]",
+            };
+            dot.push_str(&format!(
+                "    {} -> {}{};\n",
+                dot_quote(&edge.from),
+                dot_quote(&edge.to),
+                style
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// DOT中的字符串, 转义双引号, 保留已有的`\n`换行
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods_manager::{ContentPackFor, ManifestDependency, ManifestInfo};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_dependency_graph_export() {
+        let pack = ModInfo {
+            manifest_info: ManifestInfo {
+                Name: "Pack".to_string(),
+                Version: "1.0.0".to_string(),
+                UniqueId: "pack".to_string(),
+                ContentPackFor: Some(ContentPackFor {
+                    UniqueId: "Pathoschild.ContentPatcher".to_string(),
+                    MinimumVersion: None,
+                }),
+                Dependencies: vec![ManifestDependency {
+                    UniqueId: "lib".to_string(),
+                    MinimumVersion: None,
+                    IsRequired: false,
+                }],
+                ..Default::default()
+            },
+            path: PathBuf::from("pack"),
+            installed_at: None,
+        };
+        let lib = ModInfo {
+            manifest_info: ManifestInfo {
+                Name: "Lib".to_string(),
+                Version: "2.0.0".to_string(),
+                UniqueId: "Lib".to_string(),
+                ..Default::default()
+            },
+            path: PathBuf::from("lib"),
+            installed_at: None,
+        };
+        let graph = DependencyGraphExport::build(&[pack, lib]);
+
+        assert_eq!(graph.nodes.len(), 3);
+        assert!(graph.nodes[2].missing);
+        assert_eq!(graph.edges[0].kind, EdgeKind::ContentPackFor);
+        // 大小写不同的依赖指向已有节点
+        assert_eq!(graph.edges[1].to, "Lib");
+        assert_eq!(graph.edges[1].kind, EdgeKind::Optional);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("\"pack\" -> \"Lib\" [style=dashed];"));
+        assert!(dot.contains("\"Pathoschild.ContentPatcher\" [label=\"Pathoschild.ContentPatcher\\n(缺失)\", style=dashed, color=red];"));
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(json["edges"][0]["kind"], "content_pack_for");
+    }
+}
@@ -0,0 +1,382 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::SystemTime;
+
+/// 启动游戏时对一个模组的config.json所做的替换
+/// - 模组文件夹通过链接被所有配置共享, 启动前写入该配置保存的config.json, 退出后恢复
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigSwap {
+    pub unique_id: String,
+    pub config_path: PathBuf,
+    /// 启动前共享的config.json, 不存在时为None
+    pub original: Option<String>,
+    /// 写入的该配置的config.json, 没有保存过时为None
+    pub applied: Option<String>,
+}
+
+impl ConfigSwap {
+    /// 读取模组共享的config.json, 记录要写入的版本
+    /// - 与`SavesSwap`相同, 需要先把返回值写入运行记录再调用`apply`, 中途退出时才能恢复
+    /// # 参数
+    /// - `unique_id`: 模组的UniqueId
+    /// - `mod_path`: 模组文件夹
+    /// - `overlay`: 配置保存的config.json
+    pub fn new(unique_id: &str, mod_path: &Path, overlay: Option<String>) -> io::Result<Self> {
+        let config_path = mod_path.join("config.json");
+        let original = read_optional(&config_path)?;
+        Ok(ConfigSwap {
+            unique_id: unique_id.to_string(),
+            config_path,
+            original,
+            applied: overlay,
+        })
+    }
+
+    /// 写入配置保存的config.json
+    pub fn apply(&self) -> io::Result<()> {
+        if let Some(overlay) = &self.applied
+            && self.original.as_ref() != Some(overlay)
+        {
+            fs::write(&self.config_path, overlay)?;
+        }
+        Ok(())
+    }
+
+    /// 游戏退出后, 读取config.json并恢复共享的版本
+    /// # 返回值
+    /// 游戏运行期间config.json被修改(包括首次生成)时, 返回修改后的内容
+    pub fn finish(&self) -> io::Result<Option<String>> {
+        let current = read_optional(&self.config_path)?;
+        let before = self.applied.as_ref().or(self.original.as_ref());
+        let changed = current.is_some() && current.as_ref() != before;
+        self.restore_original()?;
+        Ok(if changed { current } else { None })
+    }
+
+    /// 恢复启动前共享的config.json
+    fn restore_original(&self) -> io::Result<()> {
+        let current = read_optional(&self.config_path)?;
+        if current == self.original {
+            return Ok(());
+        }
+        match &self.original {
+            Some(original) => fs::write(&self.config_path, original),
+            None => fs::remove_file(&self.config_path),
+        }
+    }
+}
+
+/// 文件不存在时返回None
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 启动游戏时用配置独立的存档目录替换游戏的存档目录
+/// - 游戏的存档目录先改名暂存, 再把配置的存档目录移动到游戏的存档位置, 退出后依次移回
+/// - 通过改名完成, 配置的存档目录需要与游戏的存档目录在同一个磁盘上
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavesSwap {
+    /// 游戏读取存档的目录
+    pub saves_path: PathBuf,
+    /// 配置独立的存档目录
+    pub profile_saves_path: PathBuf,
+    /// 运行期间游戏原有存档暂存的位置
+    pub parked_path: PathBuf,
+}
+
+impl SavesSwap {
+    /// 记录要进行的替换, 并确保配置的存档目录存在
+    /// - 需要先把返回值写入运行记录再调用`apply`, 中途退出时才能恢复
+    /// # 参数
+    /// - `saves_path`: 游戏读取存档的目录
+    /// - `profile_saves_path`: 配置独立的存档目录
+    pub fn new(saves_path: &Path, profile_saves_path: &Path) -> io::Result<Self> {
+        let name = saves_path
+            .file_name()
+            .ok_or_else(|| io::Error::other(format!("无效的存档目录{:?}", saves_path)))?
+            .to_string_lossy();
+        let parked_path = saves_path.with_file_name(format!("{}.shared", name));
+        if parked_path.exists() {
+            return Err(io::Error::other(format!(
+                "{:?}已存在, 可能有未恢复的存档替换",
+                parked_path
+            )));
+        }
+        fs::create_dir_all(profile_saves_path)?;
+        Ok(SavesSwap {
+            saves_path: saves_path.to_path_buf(),
+            profile_saves_path: profile_saves_path.to_path_buf(),
+            parked_path,
+        })
+    }
+
+    /// 把配置的存档目录换到游戏的存档位置, 失败时撤销已完成的步骤
+    pub fn apply(&self) -> io::Result<()> {
+        if self.saves_path.exists() {
+            fs::rename(&self.saves_path, &self.parked_path)?;
+        } else if let Some(parent) = self.saves_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(e) = fs::rename(&self.profile_saves_path, &self.saves_path) {
+            let _ = self.restore();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 把配置的存档移回配置的存档目录, 再恢复游戏原有的存档
+    /// - 根据磁盘上的状态判断进行到了哪一步, 可以重复调用
+    pub fn restore(&self) -> io::Result<()> {
+        if !self.profile_saves_path.exists() && self.saves_path.exists() {
+            if let Some(parent) = self.profile_saves_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&self.saves_path, &self.profile_saves_path)?;
+        }
+        if self.parked_path.exists() {
+            if self.saves_path.exists() {
+                return Err(io::Error::other(format!(
+                    "{:?}已存在, 无法恢复暂存在{:?}的存档, 需要手动处理",
+                    self.saves_path, self.parked_path
+                )));
+            }
+            fs::rename(&self.parked_path, &self.saves_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// 一次游戏运行中对文件所做的替换
+/// - 启动时写入数据目录, 正常结束时删除; 管理器中途退出后, 下次打开时据此恢复
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionState {
+    pub profile_name: String,
+    /// 启动时间, 之后保存过的存档视为用该配置游玩
+    #[serde(default = "SystemTime::now")]
+    pub started_at: SystemTime,
+    pub config_swaps: Vec<ConfigSwap>,
+    /// 配置使用独立的存档目录时的替换
+    #[serde(default)]
+    pub saves_swap: Option<SavesSwap>,
+    /// 启动的游戏进程, 游戏启动之前为None
+    #[serde(default)]
+    pub process: Option<GameProcess>,
+}
+
+impl SessionState {
+    /// 读取上次未正常结束的运行记录
+    /// # 返回值
+    /// 文件不存在时为None
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let Some(json) = read_optional(path)? else {
+            return Ok(None);
+        };
+        let state = serde_json::from_str(&json).map_err(io::Error::other)?;
+        Ok(Some(state))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// 不保存修改, 直接恢复所有共享的config.json和游戏的存档目录, 用于启动失败时
+    /// # 返回值
+    /// 无法恢复的文件, 为空时已全部恢复
+    pub fn restore(&self) -> Vec<String> {
+        let mut failures = Vec::new();
+        for swap in &self.config_swaps {
+            if let Err(e) = swap.restore_original() {
+                failures.push(format!("无法恢复{:?}: {}", swap.config_path, e));
+            }
+        }
+        if let Some(swap) = &self.saves_swap
+            && let Err(e) = swap.restore()
+        {
+            failures.push(format!("无法恢复存档目录{:?}: {}", swap.saves_path, e));
+        }
+        failures
+    }
+}
+
+/// 启动的游戏进程
+/// - 管理器重新打开时据此判断上次的游戏是否仍在运行, 运行期间不能恢复替换的文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameProcess {
+    pub pid: u32,
+    /// 进程的启动时间, 用于区分被系统重新分配给其他进程的PID, 无法读取时为None
+    pub started_at: Option<String>,
+}
+
+impl GameProcess {
+    /// 记录刚启动的进程
+    pub fn of(pid: u32) -> Self {
+        GameProcess {
+            pid,
+            started_at: process_start_time(pid),
+        }
+    }
+
+    /// 该进程是否仍在运行
+    pub fn is_running(&self) -> bool {
+        match (process_start_time(self.pid), &self.started_at) {
+            (None, _) => false,
+            (Some(now), Some(recorded)) => &now == recorded,
+            (Some(_), None) => true,
+        }
+    }
+}
+
+/// 读取进程的启动时间, 进程不存在时返回None
+#[cfg(windows)]
+fn process_start_time(pid: u32) -> Option<String> {
+    let script = format!("(Get-Process -Id {}).StartTime.ToFileTimeUtc()", pid);
+    run_query(Command::new("powershell").args(["-NoProfile", "-Command", &script]))
+}
+
+/// 读取进程的启动时间, 进程不存在时返回None
+#[cfg(not(windows))]
+fn process_start_time(pid: u32) -> Option<String> {
+    run_query(Command::new("ps").args(["-o", "lstart=", "-p", &pid.to_string()]))
+}
+
+/// 执行查询命令, 成功且有输出时返回输出
+fn run_query(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+/// 启动游戏的结果
+pub struct LaunchReport {
+    pub session: GameSession,
+    /// 启动前发现的问题, 如模组要求更高的SMAPI版本, 存档上次使用的模组不在配置中, 不影响启动
+    pub warnings: Vec<String>,
+}
+
+/// 正在运行的游戏
+/// - 由`Manager::launch_stardew_valley`在`LaunchReport`中返回, 游戏退出后交给`Manager::finish_game_session`
+pub struct GameSession {
+    child: Child,
+    state: SessionState,
+}
+
+impl GameSession {
+    pub fn new(child: Child, state: SessionState) -> Self {
+        GameSession { child, state }
+    }
+
+    pub fn profile_name(&self) -> &str {
+        &self.state.profile_name
+    }
+
+    /// 游戏是否已经退出, 不会阻塞
+    pub fn has_exited(&mut self) -> Result<bool> {
+        Ok(self.child.try_wait()?.is_some())
+    }
+
+    pub fn into_state(self) -> SessionState {
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_swap() {
+        let mod_path = PathBuf::from("./test_config_swap");
+        let _ = fs::remove_dir_all(&mod_path);
+        fs::create_dir_all(&mod_path).unwrap();
+        let config_path = mod_path.join("config.json");
+        fs::write(&config_path, "shared").unwrap();
+
+        // 写入配置的版本, 游戏修改后取回, 共享的版本被恢复
+        let swap = ConfigSwap::new("a", &mod_path, Some("profile".to_string())).unwrap();
+        // 记录之后写入之前, 共享的版本不变
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "shared");
+        swap.apply().unwrap();
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "profile");
+        fs::write(&config_path, "changed").unwrap();
+        assert_eq!(swap.finish().unwrap(), Some("changed".to_string()));
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "shared");
+
+        // 没有修改时不返回内容
+        let swap = ConfigSwap::new("a", &mod_path, Some("profile".to_string())).unwrap();
+        swap.apply().unwrap();
+        assert_eq!(swap.finish().unwrap(), None);
+
+        // 游戏首次生成的config.json归属该配置, 共享的位置保持不存在
+        fs::remove_file(&config_path).unwrap();
+        let swap = ConfigSwap::new("a", &mod_path, None).unwrap();
+        swap.apply().unwrap();
+        fs::write(&config_path, "generated").unwrap();
+        assert_eq!(swap.finish().unwrap(), Some("generated".to_string()));
+        assert!(!config_path.exists());
+
+        fs::remove_dir_all(&mod_path).unwrap();
+    }
+
+    #[test]
+    fn test_saves_swap() {
+        let root = PathBuf::from("./test_saves_swap");
+        let _ = fs::remove_dir_all(&root);
+        let saves_path = root.join("Saves");
+        let profile_saves_path = root.join("profiles").join("sve");
+        fs::create_dir_all(saves_path.join("Shared_1")).unwrap();
+
+        let swap = SavesSwap::new(&saves_path, &profile_saves_path).unwrap();
+        swap.apply().unwrap();
+        assert!(!saves_path.join("Shared_1").exists());
+        assert!(swap.parked_path.join("Shared_1").exists());
+        // 游戏在运行期间创建的存档属于该配置
+        fs::create_dir_all(saves_path.join("Sve_2")).unwrap();
+        swap.restore().unwrap();
+        assert!(saves_path.join("Shared_1").exists());
+        assert!(profile_saves_path.join("Sve_2").exists());
+        assert!(!swap.parked_path.exists());
+
+        // 只完成了第一步时中途退出, 恢复后同样回到原状, 重复恢复没有影响
+        let swap = SavesSwap::new(&saves_path, &profile_saves_path).unwrap();
+        fs::rename(&saves_path, &swap.parked_path).unwrap();
+        swap.restore().unwrap();
+        swap.restore().unwrap();
+        assert!(saves_path.join("Shared_1").exists());
+        assert!(profile_saves_path.join("Sve_2").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_game_process() {
+        // 当前进程在运行, 启动时间不同时视为PID被重新分配
+        let current = GameProcess::of(std::process::id());
+        assert!(current.started_at.is_some());
+        assert!(current.is_running());
+        let reused = GameProcess {
+            started_at: Some("earlier".to_string()),
+            ..current.clone()
+        };
+        assert!(!reused.is_running());
+
+        // 已经退出的进程
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .arg("--list")
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let exited = GameProcess::of(child.id());
+        child.wait().unwrap();
+        assert!(!exited.is_running());
+    }
+}
@@ -0,0 +1,333 @@
+use crate::mods_manager::compatibility::InstalledVersions;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 游戏主程序的文件名
+const GAME_EXECUTABLES: &[&str] = if cfg!(windows) {
+    &["Stardew Valley.exe"]
+} else {
+    &["StardewValley", "Stardew Valley"]
+};
+
+/// SMAPI启动器的文件名
+pub const SMAPI_EXECUTABLE: &str = if cfg!(windows) {
+    "StardewModdingAPI.exe"
+} else {
+    "StardewModdingAPI"
+};
+
+/// 游戏的安装来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallSource {
+    Steam,
+    Gog,
+    Xbox,
+    /// 其他常见的安装位置
+    Other,
+}
+
+impl fmt::Display for InstallSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallSource::Steam => write!(f, "Steam"),
+            InstallSource::Gog => write!(f, "GOG"),
+            InstallSource::Xbox => write!(f, "Xbox"),
+            InstallSource::Other => write!(f, "其他"),
+        }
+    }
+}
+
+/// 找到的一处游戏安装
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameInstall {
+    /// 游戏主程序所在的文件夹
+    pub game_path: PathBuf,
+    pub source: InstallSource,
+    /// 已安装SMAPI时为启动器的路径
+    pub smapi_path: Option<PathBuf>,
+}
+
+impl GameInstall {
+    /// 游戏的Mods文件夹, 可能还不存在
+    pub fn mods_path(&self) -> PathBuf {
+        self.game_path.join("Mods")
+    }
+}
+
+/// 检查文件夹中是否有游戏主程序, 并查找SMAPI
+/// # 参数
+/// - `game_path`: 可能的游戏文件夹
+/// - `source`: 安装来源
+/// # 返回值
+/// 没有游戏主程序时为None
+pub fn check_game_path(game_path: &Path, source: InstallSource) -> Option<GameInstall> {
+    if !GAME_EXECUTABLES
+        .iter()
+        .any(|name| game_path.join(name).is_file())
+    {
+        return None;
+    }
+    let smapi_path = game_path.join(SMAPI_EXECUTABLE);
+    Some(GameInstall {
+        game_path: game_path.to_path_buf(),
+        source,
+        smapi_path: smapi_path.is_file().then_some(smapi_path),
+    })
+}
+
+/// 路径是否指向存在的SMAPI启动器, 不区分扩展名和大小写
+pub fn is_smapi_launcher(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .file_stem()
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("StardewModdingAPI"))
+}
+
+/// 读取已安装的SMAPI和游戏版本
+/// - 优先读取游戏文件夹中的`*.deps.json`, 读取不到时使用SMAPI最近一次运行的日志
+/// # 参数
+/// - `smapi_path`: SMAPI启动器, 与游戏主程序在同一文件夹
+/// - `log_path`: SMAPI的日志`ErrorLogs/SMAPI-latest.txt`
+pub fn read_installed_versions(smapi_path: &Path, log_path: &Path) -> InstalledVersions {
+    let game_path = smapi_path.parent().unwrap_or(Path::new(""));
+    let mut versions = InstalledVersions {
+        smapi: deps_json_version(game_path, "StardewModdingAPI"),
+        game: deps_json_version(game_path, "Stardew Valley")
+            .or_else(|| deps_json_version(game_path, "StardewValley")),
+    };
+    if (versions.smapi.is_none() || versions.game.is_none())
+        && let Ok(log) = std::fs::read_to_string(log_path)
+    {
+        let from_log = parse_smapi_log(&log);
+        versions.smapi = versions.smapi.or(from_log.smapi);
+        versions.game = versions.game.or(from_log.game);
+    }
+    versions
+}
+
+/// .NET程序的`名称.deps.json`中, `libraries`下有一项`名称/版本`
+fn deps_json_version(game_path: &Path, name: &str) -> Option<String> {
+    let text = std::fs::read_to_string(game_path.join(format!("{}.deps.json", name))).ok()?;
+    let deps: serde_json::Value = serde_json::from_str(&text).ok()?;
+    let prefix = format!("{}/", name);
+    deps.get("libraries")?
+        .as_object()?
+        .keys()
+        .find_map(|key| key.strip_prefix(&prefix))
+        .map(str::to_string)
+}
+
+/// 从SMAPI日志的第一行读取版本, 如`SMAPI 4.0.8 with Stardew Valley 1.6.8 build 24119 on ...`
+/// # 参数
+/// - `log`: 日志的内容
+pub fn parse_smapi_log(log: &str) -> InstalledVersions {
+    let Some(line) = log
+        .lines()
+        .find(|line| line.contains(" with Stardew Valley "))
+    else {
+        return InstalledVersions::default();
+    };
+    let word_after = |marker: &str| {
+        let rest = &line[line.find(marker)? + marker.len()..];
+        rest.split_whitespace().next().map(str::to_string)
+    };
+    InstalledVersions {
+        smapi: word_after("SMAPI "),
+        game: word_after(" with Stardew Valley "),
+    }
+}
+
+/// 从Steam的libraryfolders.vdf中读取所有库文件夹
+/// - vdf是Valve的KeyValues文本格式, 只需要其中的`"path"`项
+/// # 参数
+/// - `vdf`: libraryfolders.vdf的内容
+pub fn parse_library_folders(vdf: &str) -> Vec<PathBuf> {
+    let tokens = vdf_strings(vdf);
+    tokens
+        .windows(2)
+        .filter(|pair| pair[0].eq_ignore_ascii_case("path"))
+        .map(|pair| PathBuf::from(&pair[1]))
+        .collect()
+}
+
+/// 按顺序取出vdf中所有带引号的字符串, 处理`\\`和`\"`转义
+fn vdf_strings(vdf: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut chars = vdf.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut value = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => value.extend(chars.next()),
+                _ => value.push(c),
+            }
+        }
+        strings.push(value);
+    }
+    strings
+}
+
+/// 各系统下Steam的常见安装位置
+fn steam_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if cfg!(windows) {
+        roots.push(PathBuf::from("C:/Program Files (x86)/Steam"));
+        roots.push(PathBuf::from("C:/Program Files/Steam"));
+    } else if let Some(home) = dirs::home_dir() {
+        if cfg!(target_os = "macos") {
+            roots.push(home.join("Library/Application Support/Steam"));
+        } else {
+            roots.push(home.join(".steam/steam"));
+            roots.push(home.join(".local/share/Steam"));
+            roots.push(home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+        }
+    }
+    roots
+}
+
+/// 在一个Steam安装的所有库文件夹中查找游戏
+/// # 参数
+/// - `steam_root`: Steam的安装文件夹
+pub fn find_in_steam_root(steam_root: &Path) -> Vec<GameInstall> {
+    let mut libraries = vec![steam_root.to_path_buf()];
+    if let Ok(vdf) = std::fs::read_to_string(steam_root.join("steamapps/libraryfolders.vdf")) {
+        libraries.extend(parse_library_folders(&vdf));
+    }
+    libraries
+        .iter()
+        .filter_map(|library| {
+            let game_path = library.join("steamapps/common/Stardew Valley");
+            // macOS上游戏主程序在应用包内
+            check_game_path(&game_path, InstallSource::Steam).or_else(|| {
+                check_game_path(&game_path.join("Contents/MacOS"), InstallSource::Steam)
+            })
+        })
+        .collect()
+}
+
+/// GOG, Xbox以及其他常见的安装位置
+fn other_candidates() -> Vec<(PathBuf, InstallSource)> {
+    let mut candidates = Vec::new();
+    if cfg!(windows) {
+        candidates.push((
+            PathBuf::from("C:/Program Files (x86)/GOG Galaxy/Games/Stardew Valley"),
+            InstallSource::Gog,
+        ));
+        candidates.push((
+            PathBuf::from("C:/GOG Games/Stardew Valley"),
+            InstallSource::Gog,
+        ));
+        candidates.push((
+            PathBuf::from("C:/XboxGames/Stardew Valley/Content"),
+            InstallSource::Xbox,
+        ));
+        candidates.push((
+            PathBuf::from("C:/Program Files/ModifiableWindowsApps/Stardew Valley"),
+            InstallSource::Xbox,
+        ));
+    } else if let Some(home) = dirs::home_dir() {
+        if cfg!(target_os = "macos") {
+            candidates.push((
+                PathBuf::from("/Applications/Stardew Valley.app/Contents/MacOS"),
+                InstallSource::Other,
+            ));
+        } else {
+            candidates.push((
+                home.join("GOG Games/Stardew Valley/game"),
+                InstallSource::Gog,
+            ));
+            candidates.push((home.join("Games/stardew-valley"), InstallSource::Other));
+        }
+    }
+    candidates
+}
+
+/// 在Steam的库文件夹以及GOG, Xbox和各系统的常见位置查找游戏
+/// - 只返回找到游戏主程序的位置, 同一文件夹只返回一次
+/// - 已安装SMAPI的排在前面
+pub fn detect_installs() -> Vec<GameInstall> {
+    let mut installs: Vec<GameInstall> = steam_roots()
+        .iter()
+        .flat_map(|root| find_in_steam_root(root))
+        .chain(
+            other_candidates()
+                .iter()
+                .filter_map(|(path, source)| check_game_path(path, *source)),
+        )
+        .collect();
+    let mut seen = std::collections::HashSet::new();
+    // ~/.steam/steam通常是另一个Steam位置的链接
+    installs.retain(|install| {
+        seen.insert(
+            std::fs::canonicalize(&install.game_path).unwrap_or_else(|_| install.game_path.clone()),
+        )
+    });
+    installs.sort_by_key(|install| install.smapi_path.is_none());
+    installs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find_in_steam_root() {
+        let root = PathBuf::from("./test_install_detect");
+        let _ = fs::remove_dir_all(&root);
+        let steam_root = root.join("Steam");
+        let library = root.join("SteamLibrary");
+        fs::create_dir_all(steam_root.join("steamapps")).unwrap();
+        let vdf = format!(
+            "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t\t\"apps\"\n\t\t{{\n\t\t\t\"413150\"\t\t\"0\"\n\t\t}}\n\t}}\n}}",
+            library.display().to_string().replace('\\', "\\\\")
+        );
+        assert_eq!(parse_library_folders(&vdf), std::slice::from_ref(&library));
+        fs::write(steam_root.join("steamapps/libraryfolders.vdf"), vdf).unwrap();
+
+        // 游戏在第二个库文件夹中, 且装有SMAPI
+        let game_path = library.join("steamapps/common/Stardew Valley");
+        fs::create_dir_all(&game_path).unwrap();
+        fs::write(game_path.join(GAME_EXECUTABLES[0]), "").unwrap();
+        fs::write(game_path.join(SMAPI_EXECUTABLE), "").unwrap();
+        let installs = find_in_steam_root(&steam_root);
+        assert_eq!(installs.len(), 1);
+        assert_eq!(installs[0].game_path, game_path);
+        assert_eq!(installs[0].source, InstallSource::Steam);
+        assert_eq!(
+            installs[0].smapi_path,
+            Some(game_path.join(SMAPI_EXECUTABLE))
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_read_installed_versions() {
+        let game_path = PathBuf::from("./test_installed_versions");
+        let _ = fs::remove_dir_all(&game_path);
+        fs::create_dir_all(&game_path).unwrap();
+        fs::write(
+            game_path.join("StardewModdingAPI.deps.json"),
+            r#"{"libraries": {"StardewModdingAPI/4.1.10": {"type": "project"}}}"#,
+        )
+        .unwrap();
+        let log_path = game_path.join("SMAPI-latest.txt");
+        fs::write(
+            &log_path,
+            "[12:00:00 INFO  SMAPI] SMAPI 4.0.8 with Stardew Valley 1.6.8 build 24119 on Microsoft Windows 10 Pro\n",
+        )
+        .unwrap();
+
+        // 游戏版本没有deps.json, 从日志中读取
+        let versions = read_installed_versions(&game_path.join(SMAPI_EXECUTABLE), &log_path);
+        assert_eq!(versions.smapi.as_deref(), Some("4.1.10"));
+        assert_eq!(versions.game.as_deref(), Some("1.6.8"));
+
+        fs::remove_dir_all(&game_path).unwrap();
+    }
+}
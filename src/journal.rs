@@ -0,0 +1,158 @@
+use crate::link_manager::LinkManager;
+use std::path::PathBuf;
+
+/// 已经完成的一步文件系统操作, 回滚时按相反的顺序撤销
+#[derive(Debug, Clone, PartialEq)]
+pub enum FsStep {
+    /// 新建了配置目录
+    CreatedDir(PathBuf),
+    /// 新建了目录链接
+    CreatedLink { link_path: PathBuf, target: PathBuf },
+    /// 删除了目录链接
+    RemovedLink { link_path: PathBuf, target: PathBuf },
+    /// 目录被移动到回收位置, 提交时才真正删除
+    TrashedDir { original: PathBuf, trash: PathBuf },
+    /// 模组文件夹被移动到了新位置
+    MovedDir { from: PathBuf, to: PathBuf },
+}
+
+/// 操作日志: 记录一次操作中已经完成的文件系统步骤
+/// - 与数据库事务配合使用, 见`Manager`中的`journaled`
+/// - 操作成功时`commit`, 失败时`rollback`撤销已完成的步骤
+#[derive(Debug, Default)]
+pub struct Journal {
+    steps: Vec<FsStep>,
+}
+
+impl Journal {
+    pub fn record(&mut self, step: FsStep) {
+        self.steps.push(step);
+    }
+
+    /// 操作成功, 删除回收位置中的目录
+    /// # 返回值
+    /// 无法删除的目录, 操作本身已经成功
+    pub fn commit(self) -> Vec<String> {
+        let mut failures = Vec::new();
+        for step in self.steps {
+            if let FsStep::TrashedDir { trash, .. } = step
+                && let Err(e) = std::fs::remove_dir_all(&trash)
+            {
+                failures.push(format!("无法删除{:?}: {}", trash, e));
+            }
+        }
+        failures
+    }
+
+    /// 按相反的顺序撤销已完成的步骤
+    /// - 某一步撤销失败时继续撤销其余步骤
+    /// # 参数
+    /// - `link_manager`: 用于重建或删除目录链接
+    /// # 返回值
+    /// 撤销失败的步骤, 为空时文件系统已恢复原状
+    pub fn rollback(self, link_manager: &LinkManager) -> Vec<String> {
+        let mut failures = Vec::new();
+        for step in self.steps.into_iter().rev() {
+            let result = match &step {
+                FsStep::CreatedDir(path) => std::fs::remove_dir(path).map_err(Into::into),
+                FsStep::CreatedLink { link_path, .. } => link_manager.remove_link(link_path),
+                FsStep::RemovedLink { link_path, target } => {
+                    link_manager.create_link(target, link_path)
+                }
+                FsStep::TrashedDir { original, trash } => {
+                    std::fs::rename(trash, original).map_err(Into::into)
+                }
+                FsStep::MovedDir { from, to } => std::fs::rename(to, from).map_err(Into::into),
+            };
+            if let Err(e) = result {
+                failures.push(format!("回滚{:?}失败: {}", step, e));
+            }
+        }
+        failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollback_restores_profile() {
+        let root = std::env::temp_dir().join("smm_test_journal_rollback");
+        let _ = std::fs::remove_dir_all(&root);
+        let mods_dir = root.join("Mods");
+        for name in ["A", "B"] {
+            std::fs::create_dir_all(mods_dir.join(name)).unwrap();
+        }
+        let l_m = LinkManager {
+            link_parent_path: root.join("Profiles"),
+        };
+        let profile_path = l_m.link_parent_path.join("p");
+
+        // 已提交的配置: 包含A
+        let mut journal = Journal::default();
+        l_m.create_links(&vec![mods_dir.join("A")], "p", &mut journal)
+            .unwrap();
+        journal.commit();
+
+        // 加入B, 移除A, 再删除整个配置, 然后回滚
+        let mut journal = Journal::default();
+        l_m.create_links(&vec![mods_dir.join("B")], "p", &mut journal)
+            .unwrap();
+        l_m.remove_mod_from_profile("p", mods_dir.join("A"), &mut journal)
+            .unwrap();
+        l_m.remove_profile("p", &mut journal).unwrap();
+        assert!(!profile_path.exists());
+        assert!(journal.rollback(&l_m).is_empty());
+
+        assert_eq!(
+            std::fs::read_link(profile_path.join("A")).unwrap(),
+            mods_dir.join("A")
+        );
+        assert!(profile_path.join("B").symlink_metadata().is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_rollback_moved_dir() {
+        let root = std::env::temp_dir().join("smm_test_journal_moved_dir");
+        let _ = std::fs::remove_dir_all(&root);
+        let from = root.join("Mods").join("A");
+        let to = root.join("Library").join("A");
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::create_dir_all(to.parent().unwrap()).unwrap();
+
+        let mut journal = Journal::default();
+        std::fs::rename(&from, &to).unwrap();
+        journal.record(FsStep::MovedDir {
+            from: from.clone(),
+            to: to.clone(),
+        });
+        assert!(journal.rollback(&LinkManager::default()).is_empty());
+        assert!(from.is_dir());
+        assert!(!to.exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_rollback_reports_failures() {
+        let root = std::env::temp_dir().join("smm_test_journal_failures");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("Kept")).unwrap();
+
+        // 第一步的目标已经不存在, 撤销失败; 第二步仍然被撤销
+        let mut journal = Journal::default();
+        journal.record(FsStep::MovedDir {
+            from: root.join("Gone"),
+            to: root.join("Missing"),
+        });
+        journal.record(FsStep::CreatedDir(root.join("Kept")));
+        let failures = journal.rollback(&LinkManager::default());
+        assert_eq!(failures.len(), 1);
+        assert!(!root.join("Kept").exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,5 +1,12 @@
 pub mod config;
+pub mod error;
+pub mod export;
+pub mod game_session;
+pub mod install_detect;
+pub mod journal;
 pub mod link_manager;
 pub mod mods_manager;
+pub mod save_backup;
+pub mod saves;
 pub mod total_manager;
 pub mod ui;
@@ -1,19 +1,119 @@
+use crate::error::{ManagerError, Result};
+use crate::journal::{FsStep, Journal};
+use std::collections::HashMap;
+use std::fmt;
 use std::os::windows;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// `link_parent_path`: 所有配置的父文件夹
 pub struct LinkManager {
     pub link_parent_path: PathBuf,
 }
 
+/// 配置目录与数据库记录之间的一处差异
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkIssue {
+    /// 数据库中配置包含该模组, 但配置目录下没有对应链接
+    MissingLink { link_path: PathBuf, target: PathBuf },
+    /// 链接指向的模组文件夹已不存在
+    DanglingLink { link_path: PathBuf, target: PathBuf },
+    /// 链接指向了与数据库记录不同的文件夹
+    WrongTarget {
+        link_path: PathBuf,
+        expected: PathBuf,
+        actual: PathBuf,
+    },
+    /// 配置目录下不属于数据库记录的条目
+    /// - `is_link`为false时是真实的文件或文件夹, 修复时不会删除
+    UnknownEntry { path: PathBuf, is_link: bool },
+}
+
+impl fmt::Display for LinkIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkIssue::MissingLink { link_path, target } => {
+                write!(f, "缺少链接: {:?} -> {:?}", link_path, target)
+            }
+            LinkIssue::DanglingLink { link_path, target } => {
+                write!(
+                    f,
+                    "失效链接: {:?} -> {:?} (模组文件夹不存在)",
+                    link_path, target
+                )
+            }
+            LinkIssue::WrongTarget {
+                link_path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "链接指向错误: {:?} -> {:?}, 应为 {:?}",
+                link_path, actual, expected
+            ),
+            LinkIssue::UnknownEntry { path, is_link } => {
+                if *is_link {
+                    write!(f, "多余的链接: {:?}", path)
+                } else {
+                    write!(f, "未知的文件或文件夹: {:?} (需要手动处理)", path)
+                }
+            }
+        }
+    }
+}
+
+/// 一个配置的检查结果
+pub struct ProfileIntegrityReport {
+    pub profile_name: String,
+    pub issues: Vec<LinkIssue>,
+}
+
+impl ProfileIntegrityReport {
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// 旧版本固定的配置目录, 配置文件中没有设置`profiles_root`时沿用
+pub const LEGACY_PROFILES_ROOT: &str =
+    "C:/Program Files (x86)/Steam/steamapps/common/Stardew Valley/Profiles";
+
 // 符号链接在现有的扫描中不会被识别成模组
 impl LinkManager {
     pub fn default() -> Self {
         LinkManager {
-            link_parent_path: PathBuf::from(
-                "C:/Program Files (x86)/Steam/steamapps/common/Stardew Valley/Profiles",
-            ),
+            link_parent_path: PathBuf::from(LEGACY_PROFILES_ROOT),
+        }
+    }
+
+    /// 检查配置目录所在的文件系统能否创建目录链接
+    /// - 在配置目录中建立一个临时链接后删除, 配置目录不存在时会创建
+    /// - FAT32/exFAT等文件系统, 或Windows上没有开启开发人员模式时会失败
+    pub fn check_link_support(&self) -> Result<()> {
+        // 检查失败时删除为此新建的文件夹
+        let created = self
+            .link_parent_path
+            .ancestors()
+            .take_while(|p| !p.exists())
+            .last()
+            .map(Path::to_path_buf);
+        let target = self.link_parent_path.join(".smm_link_probe_target");
+        let link = self.link_parent_path.join(".smm_link_probe");
+        let result = std::fs::create_dir_all(&target)
+            .map_err(Into::into)
+            .and_then(|_| {
+                let _ = std::fs::remove_dir_all(&link);
+                self.create_link(&target, &link)
+            })
+            .and_then(|_| self.remove_link(&link));
+        let _ = std::fs::remove_dir(&target);
+        if result.is_err()
+            && let Some(created) = created
+        {
+            let _ = std::fs::remove_dir_all(created);
         }
+        result.map_err(|e| {
+            ManagerError::Config(format!("{:?}不支持目录链接: {}", self.link_parent_path, e))
+        })
     }
     /// 创建 目录符号链接
     /// - 需要cmd的管理员权限,
@@ -21,79 +121,282 @@ impl LinkManager {
     /// # 参数
     /// - `original_dir_path`：模组实际存放的物理路径;
     /// - `link_dir_path`：命令执行后, 会创建的目录链接, 要参考self.link_partent_path
-    fn create_link(
+    pub(crate) fn create_link(
         &self,
         original_dir_path: &PathBuf,
         link_dir_path: &PathBuf,
-    ) -> std::io::Result<()> {
-        windows::fs::symlink_dir(original_dir_path, link_dir_path)?;
-        eprintln!(
-            "{:?} -> {:?} 符号链接创建成功.",
-            link_dir_path, original_dir_path
-        );
+    ) -> Result<()> {
+        windows::fs::symlink_dir(original_dir_path, link_dir_path).map_err(|source| {
+            ManagerError::Link {
+                path: link_dir_path.clone(),
+                source,
+            }
+        })?;
         Ok(())
     }
 
+    /// 删除一个目录链接, 只删除链接本身, 不会删除链接指向的文件夹
+    pub(crate) fn remove_link(&self, link_dir_path: &Path) -> Result<()> {
+        std::fs::remove_dir_all(link_dir_path).map_err(|source| ManagerError::Link {
+            path: link_dir_path.to_path_buf(),
+            source,
+        })
+    }
+
     /// 创建一个配置并加入多个目录符号链接, 也可以用作向一个配置中添加多个目录符号链接
     /// - 需要cmd的管理员权限,
     /// - 或者 系统>开发者选项>开发人员模式 打开
     /// # 参数
     /// - `mod_path_vec`：模组实际存放的物理路径的数组;
     /// - `profile_name`: 配置名称
+    /// - `journal`: 记录完成的步骤, 以便失败时回滚
     pub fn create_links(
         &self,
         mod_path_vec: &Vec<PathBuf>,
         profile_name: &str,
-    ) -> std::io::Result<()> {
+        journal: &mut Journal,
+    ) -> Result<()> {
         //如果profile不存在对应目录, 则创建
         let profile_path = self.link_parent_path.join(profile_name);
         if !profile_path.exists() {
             std::fs::create_dir_all(&profile_path)?;
+            journal.record(FsStep::CreatedDir(profile_path.clone()));
         }
 
         //接下来为参数数组中的每个模组创建目录链接
         // bug8c0096a 这里使用模组文件夹名作为链接文件夹名
         for odp in mod_path_vec {
             let mod_folder_name = odp.file_name().unwrap().to_str().unwrap();
-            let _ = self.create_link(odp, &profile_path.join(mod_folder_name));
+            let link_path = profile_path.join(mod_folder_name);
+            // 模组已在配置中时链接已经存在, 跳过
+            if std::fs::read_link(&link_path).is_ok_and(|target| target == *odp) {
+                continue;
+            }
+            self.create_link(odp, &link_path)?;
+            journal.record(FsStep::CreatedLink {
+                link_path,
+                target: odp.clone(),
+            });
         }
         Ok(())
     }
 
     /// 通过删除profile对应的link的folder, 完成删除profile在文件系统的同步
+    /// - 配置目录本就不存在时视为成功
+    /// - 目录先被移动到`.{profile_name}.trash`, 日志提交时才真正删除
     /// # 参数
     /// - `profile_name`: profile名, 与self.link_parent_path拼接成完整路径
-    pub fn remove_profile(&self, profile_name: &str) -> Result<(), String> {
-        match std::fs::remove_dir_all(self.link_parent_path.join(profile_name)) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e.to_string()),
+    /// - `journal`: 记录完成的步骤, 以便失败时回滚
+    pub fn remove_profile(&self, profile_name: &str, journal: &mut Journal) -> Result<()> {
+        let profile_path = self.link_parent_path.join(profile_name);
+        let trash_path = self
+            .link_parent_path
+            .join(format!(".{}.trash", profile_name));
+        // 上次中断留下的回收目录
+        let _ = std::fs::remove_dir_all(&trash_path);
+        match std::fs::rename(&profile_path, &trash_path) {
+            Ok(_) => {
+                journal.record(FsStep::TrashedDir {
+                    original: profile_path,
+                    trash: trash_path,
+                });
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(source) => Err(ManagerError::Link {
+                path: profile_path,
+                source,
+            }),
         }
     }
     /// 从配置中移除mod对应的目录链接
     /// # 参数
     /// - `profile_name`: profile名, 与self.link_parent_path拼接成完整路径
     /// - `mod_path`: 模组路径
+    /// - `journal`: 记录完成的步骤, 以便失败时回滚
     /// bug8c0096a 这里使用模组名(与模组文件夹名不同)拼接出 profile下链接文件夹路径
     pub fn remove_mod_from_profile(
         &self,
         profile_name: &str,
         mod_path: PathBuf,
-    ) -> Result<(), String> {
+        journal: &mut Journal,
+    ) -> Result<()> {
         let mod_folder_name = mod_path.file_name().unwrap().to_str().unwrap();
         let mod_link_path = self
             .link_parent_path
             .join(profile_name)
             .join(mod_folder_name);
-        match std::fs::remove_dir_all(mod_link_path) {
-            Ok(_) => {
-                eprintln!(
-                    "link_manager: link{:?} -> {:?} removed.",
-                    mod_folder_name, mod_path
-                );
-                Ok(())
+        // 链接已被删除时视为已移除, 也不需要在回滚时重建
+        match mod_link_path.symlink_metadata() {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            _ => {}
+        }
+        // 回滚时按链接原本的指向重建
+        let target = std::fs::read_link(&mod_link_path).unwrap_or_else(|_| mod_path.clone());
+        self.remove_link(&mod_link_path)?;
+        journal.record(FsStep::RemovedLink {
+            link_path: mod_link_path,
+            target,
+        });
+        Ok(())
+    }
+}
+
+impl LinkManager {
+    /// 比较配置目录与数据库记录
+    /// # 参数
+    /// - `profile_name`: profile名, 与self.link_parent_path拼接成完整路径
+    /// - `mod_path_vec`: 数据库中该配置包含的模组的实际路径
+    pub fn verify_profile(
+        &self,
+        profile_name: &str,
+        mod_path_vec: &[PathBuf],
+    ) -> Result<ProfileIntegrityReport> {
+        let profile_path = self.link_parent_path.join(profile_name);
+        // 链接文件夹名 -> 模组实际路径, 与create_links的命名方式一致
+        let mut expected: HashMap<std::ffi::OsString, &PathBuf> = mod_path_vec
+            .iter()
+            .filter_map(|p| p.file_name().map(|name| (name.to_os_string(), p)))
+            .collect();
+
+        let mut issues = Vec::new();
+        if profile_path.exists() {
+            for entry in std::fs::read_dir(&profile_path)? {
+                let entry = entry?;
+                let path = entry.path();
+                let is_link = entry.file_type()?.is_symlink();
+                match expected.remove(&entry.file_name()) {
+                    Some(target) if is_link => {
+                        let actual = std::fs::read_link(&path)?;
+                        if actual != *target {
+                            issues.push(LinkIssue::WrongTarget {
+                                link_path: path,
+                                expected: target.clone(),
+                                actual,
+                            });
+                        } else if !path.exists() {
+                            issues.push(LinkIssue::DanglingLink {
+                                link_path: path,
+                                target: target.clone(),
+                            });
+                        }
+                    }
+                    // 同名的真实文件夹占用了链接的位置
+                    _ => issues.push(LinkIssue::UnknownEntry { path, is_link }),
+                }
+            }
+        }
+        for (name, target) in expected {
+            issues.push(LinkIssue::MissingLink {
+                link_path: profile_path.join(name),
+                target: target.clone(),
+            });
+        }
+
+        Ok(ProfileIntegrityReport {
+            profile_name: profile_name.to_string(),
+            issues,
+        })
+    }
+
+    /// 按检查结果修复配置目录, 以数据库记录为准
+    /// - 补建缺少的链接, 重建指向错误的链接, 删除多余的链接
+    /// - 失效链接(模组文件夹已删除)和真实的文件或文件夹不处理
+    /// # 参数
+    /// - `report`: `verify_profile`的结果
+    pub fn repair_profile(&self, report: &ProfileIntegrityReport) -> Result<()> {
+        let profile_path = self.link_parent_path.join(&report.profile_name);
+        for issue in &report.issues {
+            match issue {
+                LinkIssue::MissingLink { link_path, target } => {
+                    std::fs::create_dir_all(&profile_path)?;
+                    if !link_path.exists() {
+                        self.create_link(target, link_path)?;
+                    }
+                }
+                LinkIssue::WrongTarget {
+                    link_path,
+                    expected,
+                    ..
+                } => {
+                    self.remove_link(link_path)?;
+                    self.create_link(expected, link_path)?;
+                }
+                LinkIssue::UnknownEntry {
+                    path,
+                    is_link: true,
+                } => self.remove_link(path)?,
+                LinkIssue::DanglingLink { .. } | LinkIssue::UnknownEntry { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 模组文件夹移动后, 把配置中指向原位置的链接改为指向新位置
+    /// - 配置中没有指向原位置的链接时不做任何事
+    /// # 参数
+    /// - `profile_name`: 配置名, 与self.link_parent_path拼接成完整路径
+    /// - `old_path`: 模组原来的路径
+    /// - `new_path`: 模组新的路径, 文件夹名与原来相同
+    /// - `journal`: 记录完成的步骤, 以便失败时回滚
+    pub fn relink_mod(
+        &self,
+        profile_name: &str,
+        old_path: &Path,
+        new_path: &Path,
+        journal: &mut Journal,
+    ) -> Result<()> {
+        let Some(mod_folder_name) = old_path.file_name() else {
+            return Ok(());
+        };
+        let link_path = self
+            .link_parent_path
+            .join(profile_name)
+            .join(mod_folder_name);
+        if !std::fs::read_link(&link_path).is_ok_and(|target| target == old_path) {
+            return Ok(());
+        }
+        self.remove_mod_from_profile(profile_name, old_path.to_path_buf(), journal)?;
+        self.create_links(&vec![new_path.to_path_buf()], profile_name, journal)
+    }
+
+    /// 让文件夹中的目录链接与给定的模组一致, 用于把配置的模组放进游戏的Mods文件夹
+    /// - 只删除目录链接, 真实的文件和文件夹保持不变
+    /// - 同名的真实文件夹占用了链接的位置时跳过该模组
+    /// # 参数
+    /// - `dir`: 放置链接的文件夹
+    /// - `mod_path_vec`: 模组实际存放的物理路径
+    /// # 返回值
+    /// 被跳过的模组
+    pub fn mirror_links(&self, dir: &Path, mod_path_vec: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+        let mut expected: HashMap<std::ffi::OsString, &PathBuf> = mod_path_vec
+            .iter()
+            .filter_map(|p| p.file_name().map(|name| (name.to_os_string(), p)))
+            .collect();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_symlink() {
+                continue;
             }
-            Err(e) => Err(e.to_string()),
+            let path = entry.path();
+            let target = std::fs::read_link(&path)?;
+            if expected.get(&entry.file_name()) == Some(&&target) {
+                expected.remove(&entry.file_name());
+            } else {
+                self.remove_link(&path)?;
+            }
+        }
+        let mut skipped = Vec::new();
+        for (name, target) in expected {
+            let link_path = dir.join(name);
+            if link_path.symlink_metadata().is_ok() {
+                skipped.push(target.clone());
+                continue;
+            }
+            self.create_link(target, &link_path)?;
         }
+        Ok(skipped)
     }
 }
 
@@ -130,7 +433,7 @@ mod tests {
                 "C:/Program Files (x86)/Steam/steamapps/common/Stardew Valley/Mods/ConsoleCommands",
             ),
         ];
-        let _ = l_m.create_links(&mods_path, profile_name);
+        let _ = l_m.create_links(&mods_path, profile_name, &mut Journal::default());
 
         for mp in mods_path {
             let mod_folder_name = mp.file_name().unwrap().to_str().unwrap();
@@ -141,11 +444,203 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_verify_and_repair_profile() {
+        let root = std::env::temp_dir().join("smm_test_verify_profile");
+        let _ = std::fs::remove_dir_all(&root);
+        let mods_dir = root.join("Mods");
+        for name in ["A", "B", "C"] {
+            std::fs::create_dir_all(mods_dir.join(name)).unwrap();
+        }
+        let l_m = LinkManager {
+            link_parent_path: root.join("Profiles"),
+        };
+        let profile_name = "p";
+        let profile_path = l_m.link_parent_path.join(profile_name);
+        std::fs::create_dir_all(&profile_path).unwrap();
+        // A正确, B指向了C, 多出一个指向C的链接和一个真实文件夹, 缺少D
+        l_m.create_link(&mods_dir.join("A"), &profile_path.join("A"))
+            .unwrap();
+        l_m.create_link(&mods_dir.join("C"), &profile_path.join("B"))
+            .unwrap();
+        l_m.create_link(&mods_dir.join("C"), &profile_path.join("C"))
+            .unwrap();
+        std::fs::create_dir_all(profile_path.join("Stray")).unwrap();
+        std::fs::create_dir_all(mods_dir.join("D")).unwrap();
+        let expected = vec![mods_dir.join("A"), mods_dir.join("B"), mods_dir.join("D")];
+
+        let report = l_m.verify_profile(profile_name, &expected).unwrap();
+        assert_eq!(report.issues.len(), 4);
+        assert!(report.issues.contains(&LinkIssue::MissingLink {
+            link_path: profile_path.join("D"),
+            target: mods_dir.join("D"),
+        }));
+        assert!(report.issues.contains(&LinkIssue::WrongTarget {
+            link_path: profile_path.join("B"),
+            expected: mods_dir.join("B"),
+            actual: mods_dir.join("C"),
+        }));
+
+        l_m.repair_profile(&report).unwrap();
+        let report = l_m.verify_profile(profile_name, &expected).unwrap();
+        assert_eq!(
+            report.issues,
+            vec![LinkIssue::UnknownEntry {
+                path: profile_path.join("Stray"),
+                is_link: false,
+            }]
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_remove_missing_link() {
+        let root = std::env::temp_dir().join("smm_test_remove_missing_link");
+        let _ = std::fs::remove_dir_all(&root);
+        let mod_path = root.join("Mods").join("A");
+        std::fs::create_dir_all(&mod_path).unwrap();
+        let l_m = LinkManager {
+            link_parent_path: root.join("Profiles"),
+        };
+        let mut journal = Journal::default();
+        for profile in ["p1", "p2"] {
+            l_m.create_links(&vec![mod_path.clone()], profile, &mut journal)
+                .unwrap();
+        }
+        journal.commit();
+        // 用户已经手动删除了p1中的链接
+        l_m.remove_link(&l_m.link_parent_path.join("p1").join("A"))
+            .unwrap();
+
+        let mut journal = Journal::default();
+        for profile in ["p1", "p2"] {
+            l_m.remove_mod_from_profile(profile, mod_path.clone(), &mut journal)
+                .unwrap();
+        }
+        assert!(
+            l_m.link_parent_path
+                .join("p2")
+                .join("A")
+                .symlink_metadata()
+                .is_err()
+        );
+        // 回滚只重建实际删除的链接
+        journal.rollback(&l_m);
+        assert!(
+            l_m.link_parent_path
+                .join("p1")
+                .join("A")
+                .symlink_metadata()
+                .is_err()
+        );
+        assert_eq!(
+            std::fs::read_link(l_m.link_parent_path.join("p2").join("A")).unwrap(),
+            mod_path
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_relink_mod() {
+        let root = std::env::temp_dir().join("smm_test_relink_mod");
+        let _ = std::fs::remove_dir_all(&root);
+        let old_path = root.join("Mods").join("A");
+        let new_path = root.join("Library").join("A");
+        std::fs::create_dir_all(&old_path).unwrap();
+        let l_m = LinkManager {
+            link_parent_path: root.join("Profiles"),
+        };
+        let mut journal = Journal::default();
+        l_m.create_links(&vec![old_path.clone()], "p", &mut journal)
+            .unwrap();
+        journal.commit();
+        std::fs::create_dir_all(root.join("Library")).unwrap();
+        std::fs::rename(&old_path, &new_path).unwrap();
+
+        let link_path = l_m.link_parent_path.join("p").join("A");
+        let mut journal = Journal::default();
+        l_m.relink_mod("p", &old_path, &new_path, &mut journal)
+            .unwrap();
+        assert_eq!(std::fs::read_link(&link_path).unwrap(), new_path);
+        // 没有指向原位置的链接时不做任何事
+        l_m.relink_mod("q", &old_path, &new_path, &mut journal)
+            .unwrap();
+        assert!(!l_m.link_parent_path.join("q").exists());
+
+        journal.rollback(&l_m);
+        assert_eq!(std::fs::read_link(&link_path).unwrap(), old_path);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_check_link_support() {
+        let root = std::env::temp_dir().join("smm_test_link_support");
+        let _ = std::fs::remove_dir_all(&root);
+        let l_m = LinkManager {
+            link_parent_path: root.join("Profiles"),
+        };
+        l_m.check_link_support().unwrap();
+        // 检查后不留下临时文件
+        assert_eq!(std::fs::read_dir(&l_m.link_parent_path).unwrap().count(), 0);
+
+        // 检查失败时删除新建的文件夹: 父路径是文件, 无法创建
+        std::fs::write(root.join("file"), "").unwrap();
+        let l_m = LinkManager {
+            link_parent_path: root.join("file").join("Profiles"),
+        };
+        assert!(l_m.check_link_support().is_err());
+        assert!(root.join("file").is_file());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_mirror_links() {
+        let root = std::env::temp_dir().join("smm_test_mirror_links");
+        let _ = std::fs::remove_dir_all(&root);
+        let library = root.join("Library");
+        for name in ["A", "B"] {
+            std::fs::create_dir_all(library.join(name)).unwrap();
+        }
+        let game_mods = root.join("Mods");
+        // 游戏自带的真实文件夹不受影响
+        std::fs::create_dir_all(game_mods.join("ConsoleCommands")).unwrap();
+        let l_m = LinkManager {
+            link_parent_path: root.join("Profiles"),
+        };
+
+        l_m.mirror_links(&game_mods, &[library.join("A")]).unwrap();
+        l_m.mirror_links(&game_mods, &[library.join("B")]).unwrap();
+        // 与真实文件夹同名的模组被跳过并返回
+        std::fs::create_dir_all(library.join("ConsoleCommands")).unwrap();
+        let skipped = l_m
+            .mirror_links(
+                &game_mods,
+                &[library.join("B"), library.join("ConsoleCommands")],
+            )
+            .unwrap();
+        assert_eq!(skipped, vec![library.join("ConsoleCommands")]);
+        assert!(game_mods.join("A").symlink_metadata().is_err());
+        assert_eq!(
+            std::fs::read_link(game_mods.join("B")).unwrap(),
+            library.join("B")
+        );
+        assert!(game_mods.join("ConsoleCommands").is_dir());
+        assert!(library.join("A").is_dir());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
     #[test]
     fn test_remove_profile() {
         let l_m = LinkManager::default();
 
         let profile_name = "test_profile";
-        let _ = l_m.remove_profile(profile_name);
+        let mut journal = Journal::default();
+        let _ = l_m.remove_profile(profile_name, &mut journal);
+        journal.commit();
     }
 }
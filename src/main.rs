@@ -1,6 +1,45 @@
+use StardewModsManager::total_manager::Manager;
 use StardewModsManager::ui::StardewModsManagerApp;
 use eframe::egui;
+use std::path::PathBuf;
+
+/// 命令行参数`--export-graph <路径> [--profile <配置名>]`
+/// - 导出依赖图后直接退出, 不打开界面
+/// # 返回值
+/// 没有`--export-graph`时返回None
+fn export_graph_from_args(args: &[String]) -> Option<Result<PathBuf, String>> {
+    let position = args.iter().position(|a| a == "--export-graph")?;
+    let Some(path) = args.get(position + 1) else {
+        return Some(Err("--export-graph需要文件路径".to_string()));
+    };
+    let profile = match args.iter().position(|a| a == "--profile") {
+        Some(i) => match args.get(i + 1) {
+            Some(name) => Some(name.as_str()),
+            None => return Some(Err("--profile需要配置名".to_string())),
+        },
+        None => None,
+    };
+    let path = PathBuf::from(path);
+    let result = Manager::default()
+        .and_then(|manager| manager.export_dependency_graph(profile, &path))
+        .map(|_| path)
+        .map_err(|e| e.to_string());
+    Some(result)
+}
+
 fn main() -> eframe::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = export_graph_from_args(&args) {
+        match result {
+            Ok(path) => println!("依赖图已导出到{}", path.display()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_fullscreen(false) // 启动时全屏
@@ -32,7 +71,7 @@ fn main() -> eframe::Result<()> {
                 .into();
             });
 
-            Ok(Box::new(StardewModsManagerApp::new(cc)))
+            Ok(Box::new(StardewModsManagerApp::new(cc)?))
         }),
     )
 }
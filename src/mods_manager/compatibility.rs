@@ -0,0 +1,121 @@
+use super::ModInfo;
+use super::mods_query::compare_versions;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// 已安装的SMAPI和游戏版本, 读取不到时为None
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InstalledVersions {
+    #[serde(default)]
+    pub smapi: Option<String>,
+    #[serde(default)]
+    pub game: Option<String>,
+}
+
+/// 模组对SMAPI或游戏版本的要求
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Requirement {
+    /// manifest中的MinimumApiVersion
+    Smapi,
+    /// manifest中的MinimumGameVersion
+    Game,
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Requirement::Smapi => write!(f, "SMAPI"),
+            Requirement::Game => write!(f, "游戏"),
+        }
+    }
+}
+
+/// 一个不满足的版本要求
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnmetRequirement {
+    pub unique_id: String,
+    pub requirement: Requirement,
+    pub minimum: String,
+    pub installed: String,
+}
+
+impl fmt::Display for UnmetRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "需要{} {}, 当前为{}",
+            self.requirement, self.minimum, self.installed
+        )
+    }
+}
+
+/// 找出最低SMAPI或游戏版本高于已安装版本的模组
+/// - 读取不到已安装版本时不检查对应的要求
+/// # 参数
+/// - `mods`: 要检查的模组
+/// - `installed`: 已安装的版本
+pub fn unmet_requirements(
+    mods: &[ModInfo],
+    installed: &InstalledVersions,
+) -> Vec<UnmetRequirement> {
+    let mut unmet = Vec::new();
+    for m in mods {
+        let manifest = &m.manifest_info;
+        let checks = [
+            (
+                Requirement::Smapi,
+                &manifest.MinimumApiVersion,
+                &installed.smapi,
+            ),
+            (
+                Requirement::Game,
+                &manifest.MinimumGameVersion,
+                &installed.game,
+            ),
+        ];
+        for (requirement, minimum, installed) in checks {
+            if let (Some(minimum), Some(installed)) = (minimum, installed)
+                && compare_versions(installed, minimum) == Ordering::Less
+            {
+                unmet.push(UnmetRequirement {
+                    unique_id: manifest.UniqueId.clone(),
+                    requirement,
+                    minimum: minimum.clone(),
+                    installed: installed.clone(),
+                });
+            }
+        }
+    }
+    unmet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods_manager::test_support::sample_mod;
+
+    #[test]
+    fn test_unmet_requirements() {
+        let mods = [
+            sample_mod("ok")
+                .minimum_api_version("3.18.0")
+                .minimum_game_version("1.5.6")
+                .build(),
+            sample_mod("new.smapi").minimum_api_version("4.1.0").build(),
+            sample_mod("new.game").minimum_game_version("1.6.9").build(),
+        ];
+        let installed = InstalledVersions {
+            smapi: Some("4.0.8".to_string()),
+            game: Some("1.6.8.24119".to_string()),
+        };
+        let unmet = unmet_requirements(&mods, &installed);
+        assert_eq!(unmet.len(), 2);
+        assert_eq!(unmet[0].unique_id, "new.smapi");
+        assert_eq!(unmet[0].to_string(), "需要SMAPI 4.1.0, 当前为4.0.8");
+        assert_eq!(unmet[1].requirement, Requirement::Game);
+
+        // 版本未知时不检查
+        assert!(unmet_requirements(&mods, &InstalledVersions::default()).is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::error::{ManagerError, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// config.json中一个字段的类型, 决定编辑时使用的控件
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigField {
+    Bool,
+    Integer,
+    Number,
+    Text,
+    /// 只能取给定的值, 来自内容包content.json中ConfigSchema的AllowValues
+    /// - `multiple`为true时可以用逗号分隔多个值
+    Choice {
+        values: Vec<String>,
+        multiple: bool,
+        allow_blank: bool,
+    },
+    /// 嵌套的对象, 字段按名称排序
+    Object(Vec<(String, ConfigField)>),
+    /// 数组和null, 只能编辑json文本
+    Raw,
+}
+
+/// 内容包的ConfigSchema中一个字段的可选值
+/// - 格式与Content Patcher相同, Generic Mod Config Menu也据此生成下拉框
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllowedValues {
+    pub values: Vec<String>,
+    pub multiple: bool,
+    pub allow_blank: bool,
+}
+
+/// 从content.json中读取ConfigSchema
+/// # 参数
+/// - `content`: content.json的内容
+/// # 返回值
+/// 小写字段名 -> 可选值, 只包含设置了AllowValues的字段
+pub fn parse_config_schema(content: &Value) -> HashMap<String, AllowedValues> {
+    let Some(schema) = content.get("ConfigSchema").and_then(Value::as_object) else {
+        return HashMap::new();
+    };
+    schema
+        .iter()
+        .filter_map(|(name, field)| {
+            let values = field.get("AllowValues")?.as_str()?;
+            let flag = |key: &str| field.get(key).and_then(Value::as_bool).unwrap_or(false);
+            Some((
+                name.to_lowercase(),
+                AllowedValues {
+                    values: split_values(values),
+                    multiple: flag("AllowMultiple"),
+                    allow_blank: flag("AllowBlank"),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// 读取模组文件夹中content.json的ConfigSchema
+/// - 不是内容包(没有content.json)时返回空
+/// # 参数
+/// - `mod_folder_path`: 模组文件夹路径
+/// # 返回值
+/// content.json无法读取或解析(如含有注释)时返回错误
+pub fn read_allowed_values(mod_folder_path: &Path) -> Result<HashMap<String, AllowedValues>> {
+    let content_path = mod_folder_path.join("content.json");
+    let text = match std::fs::read_to_string(&content_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let content = serde_json::from_str(text.trim_start_matches('\u{feff}'))
+        .map_err(|e| ManagerError::Config(format!("无法解析{:?}: {}", content_path, e)))?;
+    Ok(parse_config_schema(&content))
+}
+
+/// 按逗号分隔, 去掉空白和空项
+fn split_values(values: &str) -> Vec<String> {
+    values
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 根据config.json的内容推断每个字段的类型
+/// # 参数
+/// - `value`: config.json的内容
+/// - `allowed`: 顶层字段的可选值, 见`parse_config_schema`
+pub fn infer_schema(value: &Value, allowed: &HashMap<String, AllowedValues>) -> ConfigField {
+    match value {
+        Value::Object(map) => ConfigField::Object(
+            map.iter()
+                .map(|(name, field)| {
+                    let schema = match (field, allowed.get(&name.to_lowercase())) {
+                        (Value::String(_), Some(allowed)) => ConfigField::Choice {
+                            values: allowed.values.clone(),
+                            multiple: allowed.multiple,
+                            allow_blank: allowed.allow_blank,
+                        },
+                        // 可选值只适用于内容包扁平的顶层字段
+                        _ => infer_schema(field, &HashMap::new()),
+                    };
+                    (name.clone(), schema)
+                })
+                .collect(),
+        ),
+        _ => infer_field(value),
+    }
+}
+
+fn infer_field(value: &Value) -> ConfigField {
+    match value {
+        Value::Bool(_) => ConfigField::Bool,
+        Value::Number(n) if n.is_i64() || n.is_u64() => ConfigField::Integer,
+        Value::Number(_) => ConfigField::Number,
+        Value::String(_) => ConfigField::Text,
+        Value::Object(_) => infer_schema(value, &HashMap::new()),
+        Value::Array(_) | Value::Null => ConfigField::Raw,
+    }
+}
+
+/// 检查修改后的config.json是否符合推断出的类型
+/// # 参数
+/// - `schema`: 推断出的类型
+/// - `value`: 修改后的内容
+/// # 返回值
+/// 所有不符合的字段及原因, 为空时通过检查
+pub fn validate(schema: &ConfigField, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_field(schema, value, "config", &mut errors);
+    errors
+}
+
+fn validate_field(schema: &ConfigField, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let expected = match schema {
+        ConfigField::Bool if !value.is_boolean() => "布尔值",
+        ConfigField::Integer if !(value.is_i64() || value.is_u64()) => "整数",
+        ConfigField::Number if !value.is_number() => "数字",
+        ConfigField::Text if !value.is_string() => "字符串",
+        ConfigField::Choice {
+            values,
+            multiple,
+            allow_blank,
+        } => {
+            let Some(text) = value.as_str() else {
+                errors.push(format!("{}: 应为字符串", path));
+                return;
+            };
+            let items = if *multiple {
+                split_values(text)
+            } else {
+                vec![text.trim().to_string()]
+                    .into_iter()
+                    .filter(|v| !v.is_empty())
+                    .collect()
+            };
+            if items.is_empty() && !allow_blank {
+                errors.push(format!("{}: 不能为空", path));
+            }
+            if items.len() > 1 && !multiple {
+                errors.push(format!("{}: 只能选择一个值", path));
+            }
+            for item in items {
+                if !values.iter().any(|v| v.eq_ignore_ascii_case(&item)) {
+                    errors.push(format!(
+                        "{}: {}不是可选的值({})",
+                        path,
+                        item,
+                        values.join(", ")
+                    ));
+                }
+            }
+            return;
+        }
+        ConfigField::Object(fields) => {
+            let Some(map) = value.as_object() else {
+                errors.push(format!("{}: 应为对象", path));
+                return;
+            };
+            for (name, field) in fields {
+                let field_path = format!("{}.{}", path, name);
+                match map.get(name) {
+                    Some(v) => validate_field(field, v, &field_path, errors),
+                    None => errors.push(format!("{}: 缺少字段", field_path)),
+                }
+            }
+            return;
+        }
+        _ => return,
+    };
+    errors.push(format!("{}: 应为{}", path, expected));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_and_validate() {
+        let content = json!({
+            "ConfigSchema": {
+                "Material": { "AllowValues": "Wood, Metal", "Default": "Wood" },
+                "Seasons": { "AllowValues": "spring, summer, fall", "AllowMultiple": true },
+            }
+        });
+        let allowed = parse_config_schema(&content);
+        let config = json!({
+            "Enabled": true,
+            "Speed": 2,
+            "Scale": 1.5,
+            "Material": "Wood",
+            "Seasons": "spring, fall",
+            "Keys": { "Toggle": "F5" },
+            "Ignored": [],
+        });
+        let schema = infer_schema(&config, &allowed);
+        let ConfigField::Object(fields) = &schema else {
+            panic!("顶层应为对象");
+        };
+        let field = |name: &str| &fields.iter().find(|(n, _)| n == name).unwrap().1;
+        assert_eq!(*field("Enabled"), ConfigField::Bool);
+        assert_eq!(*field("Speed"), ConfigField::Integer);
+        assert_eq!(*field("Scale"), ConfigField::Number);
+        assert_eq!(*field("Ignored"), ConfigField::Raw);
+        assert_eq!(
+            *field("Keys"),
+            ConfigField::Object(vec![("Toggle".to_string(), ConfigField::Text)])
+        );
+        assert!(matches!(
+            field("Seasons"),
+            ConfigField::Choice { multiple: true, .. }
+        ));
+        assert!(validate(&schema, &config).is_empty());
+
+        let mut edited = config.clone();
+        edited["Speed"] = json!(2.5);
+        edited["Material"] = json!("Stone");
+        edited["Keys"] = json!({});
+        assert_eq!(
+            validate(&schema, &edited),
+            [
+                "config.Keys.Toggle: 缺少字段",
+                "config.Material: Stone不是可选的值(Wood, Metal)",
+                "config.Speed: 应为整数",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_allowed_values() {
+        let mod_path = std::env::temp_dir().join("smm_test_read_allowed_values");
+        let _ = std::fs::remove_dir_all(&mod_path);
+        std::fs::create_dir_all(&mod_path).unwrap();
+        // 不是内容包
+        assert!(read_allowed_values(&mod_path).unwrap().is_empty());
+
+        std::fs::write(
+            mod_path.join("content.json"),
+            r#"{"ConfigSchema": {"Material": {"AllowValues": "Wood, Metal"}}}"#,
+        )
+        .unwrap();
+        let allowed = read_allowed_values(&mod_path).unwrap();
+        assert_eq!(allowed["material"].values, vec!["Wood", "Metal"]);
+
+        // 无法解析时返回错误, 而不是当作没有可选值
+        std::fs::write(mod_path.join("content.json"), "{ // comment\n}").unwrap();
+        assert!(read_allowed_values(&mod_path).is_err());
+
+        let _ = std::fs::remove_dir_all(&mod_path);
+    }
+}
@@ -0,0 +1,114 @@
+use super::ModInfo;
+use std::collections::{BTreeSet, HashMap};
+
+/// 已注册模组之间的依赖关系
+/// - 内容包的框架模组(ContentPackFor)视为必需依赖
+/// - SMAPI中UniqueId不区分大小写, 图中统一使用小写作为键
+pub struct DependencyGraph {
+    /// 小写UniqueId -> 原始UniqueId, 只包含已注册的模组
+    unique_ids: HashMap<String, String>,
+    /// 小写UniqueId -> 直接依赖它的模组(小写UniqueId, 是否必需)
+    dependents: HashMap<String, Vec<(String, bool)>>,
+}
+
+impl DependencyGraph {
+    /// 根据模组的manifest建立依赖图
+    /// # 参数
+    /// - `mods`: 已注册的模组
+    pub fn build(mods: &[ModInfo]) -> Self {
+        let mut unique_ids = HashMap::new();
+        let mut dependents: HashMap<String, Vec<(String, bool)>> = HashMap::new();
+        for m in mods {
+            let unique_id = m.manifest_info.UniqueId.to_lowercase();
+            for dependency in m.manifest_info.all_dependencies() {
+                dependents
+                    .entry(dependency.UniqueId.to_lowercase())
+                    .or_default()
+                    .push((unique_id.clone(), dependency.IsRequired));
+            }
+            unique_ids.insert(unique_id, m.manifest_info.UniqueId.clone());
+        }
+        DependencyGraph {
+            unique_ids,
+            dependents,
+        }
+    }
+
+    /// 直接依赖某个模组的已注册模组
+    /// # 返回值
+    /// (UniqueId, 是否必需)的数组, 按UniqueId排序
+    pub fn dependents_of(&self, unique_id: &str) -> Vec<(String, bool)> {
+        let mut ans: Vec<(String, bool)> = self
+            .dependents
+            .get(&unique_id.to_lowercase())
+            .into_iter()
+            .flatten()
+            .filter_map(|(id, required)| Some((self.unique_ids.get(id)?.clone(), *required)))
+            .collect();
+        ans.sort();
+        ans
+    }
+
+    /// 移除`removed`中的模组后, 因缺少必需依赖而无法加载的模组
+    /// - 沿必需依赖传递: A必需B, B必需C, 移除C后A和B都无法加载
+    /// # 参数
+    /// - `removed`: 被移除的模组的UniqueId
+    /// - `scope`: 只考虑这些模组, 如某个配置中的模组; 为None时考虑所有已注册模组
+    /// # 返回值
+    /// 无法加载的模组的UniqueId, 不包括`removed`本身
+    pub fn broken_by_removal(&self, removed: &[&str], scope: Option<&[&str]>) -> BTreeSet<String> {
+        let in_scope: Option<BTreeSet<String>> =
+            scope.map(|ids| ids.iter().map(|id| id.to_lowercase()).collect());
+        let mut broken = BTreeSet::new();
+        let mut stack: Vec<String> = removed.iter().map(|id| id.to_lowercase()).collect();
+        let removed: BTreeSet<String> = stack.iter().cloned().collect();
+        while let Some(current) = stack.pop() {
+            for (dependent, required) in self.dependents.get(&current).into_iter().flatten() {
+                let in_scope = in_scope.as_ref().is_none_or(|s| s.contains(dependent));
+                if *required
+                    && in_scope
+                    && !removed.contains(dependent)
+                    && broken.insert(dependent.clone())
+                {
+                    stack.push(dependent.clone());
+                }
+            }
+        }
+        broken
+            .into_iter()
+            .filter_map(|id| self.unique_ids.get(&id).cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods_manager::test_support::sample_mod;
+
+    #[test]
+    fn test_dependents_and_removal() {
+        // Framework <- Lib(必需) <- App(必需), Extra可选依赖Lib, Pack是Framework的内容包
+        let mods = [
+            sample_mod("Framework").build(),
+            sample_mod("Lib").dependency("framework", true).build(),
+            sample_mod("App").dependency("Lib", true).build(),
+            sample_mod("Extra").dependency("Lib", false).build(),
+            sample_mod("Pack").content_pack_for("Framework").build(),
+        ];
+        let graph = DependencyGraph::build(&mods);
+
+        assert_eq!(
+            graph.dependents_of("lib"),
+            vec![("App".to_string(), true), ("Extra".to_string(), false)]
+        );
+        let broken = graph.broken_by_removal(&["Framework"], None);
+        assert_eq!(
+            broken.into_iter().collect::<Vec<_>>(),
+            vec!["App", "Lib", "Pack"]
+        );
+        // 配置中没有Lib时, App不受影响
+        let broken = graph.broken_by_removal(&["Framework"], Some(&["Framework", "App", "Pack"]));
+        assert_eq!(broken.into_iter().collect::<Vec<_>>(), vec!["Pack"]);
+    }
+}
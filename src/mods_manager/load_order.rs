@@ -0,0 +1,283 @@
+use super::ModInfo;
+use super::mods_query::compare_versions;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// 模组无法加载的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadFailureReason {
+    /// 缺少必需依赖
+    MissingDependency(String),
+    /// 必需依赖的版本低于要求
+    DependencyTooOld {
+        unique_id: String,
+        minimum: String,
+        installed: String,
+    },
+    /// 依赖链回到了自身, 从被依赖的模组开始, 到它本身结束
+    CircularReference(Vec<String>),
+    /// 必需依赖本身无法加载
+    DependencyFailed(String),
+}
+
+impl fmt::Display for LoadFailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadFailureReason::MissingDependency(id) => write!(f, "缺少必需依赖{}", id),
+            LoadFailureReason::DependencyTooOld {
+                unique_id,
+                minimum,
+                installed,
+            } => write!(
+                f,
+                "依赖{}的版本{}低于要求的{}",
+                unique_id, installed, minimum
+            ),
+            LoadFailureReason::CircularReference(chain) => {
+                write!(f, "循环依赖: {}", chain.join(" => "))
+            }
+            LoadFailureReason::DependencyFailed(id) => write!(f, "依赖{}无法加载", id),
+        }
+    }
+}
+
+/// 无法加载的模组
+#[derive(Debug, Clone)]
+pub struct LoadFailure {
+    pub mod_info: ModInfo,
+    pub reason: LoadFailureReason,
+}
+
+/// 按SMAPI的规则计算出的加载顺序
+#[derive(Debug, Clone, Default)]
+pub struct LoadOrder {
+    /// 能加载的模组, 按加载顺序
+    pub order: Vec<ModInfo>,
+    /// 无法加载的模组, 按发现的先后
+    pub failures: Vec<LoadFailure>,
+}
+
+impl LoadOrder {
+    /// 所有循环依赖的依赖链
+    pub fn cycles(&self) -> Vec<&[String]> {
+        self.failures
+            .iter()
+            .filter_map(|failure| match &failure.reason {
+                LoadFailureReason::CircularReference(chain) => Some(chain.as_slice()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Queued,
+    Checking,
+    Sorted,
+    Failed,
+}
+
+/// 按SMAPI的规则计算加载顺序
+/// - 先按名称排序, 再对每个模组深度优先地先加载它的依赖(包括存在的可选依赖和内容包的框架)
+/// - 缺少必需依赖, 必需依赖版本过低或无法加载的模组不会加载
+/// - 深度优先时遇到正在检查的模组即为循环依赖, 发现循环的模组不会加载, 依赖它的模组也随之失败
+/// # 参数
+/// - `mods`: 一个配置中的模组
+pub fn resolve_load_order(mods: &[ModInfo]) -> LoadOrder {
+    let mut resolver = Resolver {
+        mods,
+        index: mods
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (m.manifest_info.UniqueId.to_lowercase(), i))
+            .collect(),
+        states: vec![State::Queued; mods.len()],
+        order: Vec::new(),
+        failures: Vec::new(),
+    };
+    let mut by_name: Vec<usize> = (0..mods.len()).collect();
+    by_name.sort_by_key(|&i| mods[i].manifest_info.Name.to_lowercase());
+    for i in by_name {
+        resolver.process(i, &mut Vec::new());
+    }
+    LoadOrder {
+        order: resolver
+            .order
+            .into_iter()
+            .map(|i| mods[i].clone())
+            .collect(),
+        failures: resolver.failures,
+    }
+}
+
+struct Resolver<'a> {
+    mods: &'a [ModInfo],
+    /// 小写UniqueId -> 在`mods`中的下标
+    index: HashMap<String, usize>,
+    states: Vec<State>,
+    order: Vec<usize>,
+    failures: Vec<LoadFailure>,
+}
+
+impl Resolver<'_> {
+    /// 处理一个模组及其依赖, 返回它能否加载
+    /// # 参数
+    /// - `chain`: 当前依赖链上正在检查的模组
+    fn process(&mut self, i: usize, chain: &mut Vec<usize>) -> bool {
+        match self.states[i] {
+            State::Sorted => return true,
+            State::Failed => return false,
+            State::Queued | State::Checking => {}
+        }
+        self.states[i] = State::Checking;
+        chain.push(i);
+        let result = self.check_dependencies(i, chain);
+        chain.pop();
+        match result {
+            Ok(()) => {
+                self.states[i] = State::Sorted;
+                self.order.push(i);
+                true
+            }
+            Err(reason) => {
+                self.states[i] = State::Failed;
+                self.failures.push(LoadFailure {
+                    mod_info: self.mods[i].clone(),
+                    reason,
+                });
+                false
+            }
+        }
+    }
+
+    fn check_dependencies(
+        &mut self,
+        i: usize,
+        chain: &mut Vec<usize>,
+    ) -> Result<(), LoadFailureReason> {
+        let mods = self.mods;
+        for dependency in mods[i].manifest_info.all_dependencies() {
+            let Some(&j) = self.index.get(&dependency.UniqueId.to_lowercase()) else {
+                if dependency.IsRequired {
+                    return Err(LoadFailureReason::MissingDependency(dependency.UniqueId));
+                }
+                continue;
+            };
+            if j == i {
+                continue;
+            }
+            let installed = &mods[j].manifest_info.Version;
+            if let Some(minimum) = &dependency.MinimumVersion
+                && dependency.IsRequired
+                && compare_versions(installed, minimum) == Ordering::Less
+            {
+                return Err(LoadFailureReason::DependencyTooOld {
+                    unique_id: mods[j].manifest_info.UniqueId.clone(),
+                    minimum: minimum.clone(),
+                    installed: installed.clone(),
+                });
+            }
+            if self.states[j] == State::Checking {
+                let start = chain.iter().position(|&k| k == j).unwrap_or(0);
+                let cycle = chain[start..]
+                    .iter()
+                    .chain(std::iter::once(&j))
+                    .map(|&k| mods[k].manifest_info.UniqueId.clone())
+                    .collect();
+                return Err(LoadFailureReason::CircularReference(cycle));
+            }
+            if !self.process(j, chain) && dependency.IsRequired {
+                return Err(LoadFailureReason::DependencyFailed(
+                    mods[j].manifest_info.UniqueId.clone(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods_manager::test_support::sample_mod;
+
+    fn ids(mods: &[ModInfo]) -> Vec<&str> {
+        mods.iter()
+            .map(|m| m.manifest_info.UniqueId.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_load_order() {
+        let pack = sample_mod("APack")
+            .name("APack")
+            .version("1.0.0")
+            .content_pack_for("framework")
+            .build();
+        let mods = [
+            pack,
+            sample_mod("Framework")
+                .name("Framework")
+                .version("2.0.0")
+                .dependency("Missing", false)
+                .build(),
+            sample_mod("Broken")
+                .name("Broken")
+                .version("1.0.0")
+                .dependency("Missing", true)
+                .build(),
+            sample_mod("Cycle1")
+                .name("Cycle1")
+                .version("1.0.0")
+                .dependency("Cycle2", true)
+                .build(),
+            sample_mod("Cycle2")
+                .name("Cycle2")
+                .version("1.0.0")
+                .dependency("Cycle1", false)
+                .build(),
+            sample_mod("NeedsBroken")
+                .name("NeedsBroken")
+                .version("1.0.0")
+                .dependency("Broken", true)
+                .build(),
+        ];
+        let load_order = resolve_load_order(&mods);
+
+        // 内容包排在框架之后, 缺失的可选依赖不影响加载
+        assert_eq!(ids(&load_order.order), ["Framework", "APack"]);
+        let failures: Vec<_> = load_order
+            .failures
+            .iter()
+            .map(|f| (f.mod_info.manifest_info.UniqueId.as_str(), f.reason.clone()))
+            .collect();
+        assert_eq!(
+            failures,
+            [
+                (
+                    "Broken",
+                    LoadFailureReason::MissingDependency("Missing".to_string())
+                ),
+                (
+                    "Cycle2",
+                    LoadFailureReason::CircularReference(vec![
+                        "Cycle1".to_string(),
+                        "Cycle2".to_string(),
+                        "Cycle1".to_string(),
+                    ])
+                ),
+                (
+                    "Cycle1",
+                    LoadFailureReason::DependencyFailed("Cycle2".to_string())
+                ),
+                (
+                    "NeedsBroken",
+                    LoadFailureReason::DependencyFailed("Broken".to_string())
+                ),
+            ]
+        );
+        assert_eq!(load_order.cycles().len(), 1);
+    }
+}
@@ -0,0 +1,320 @@
+use rusqlite::{Connection, Result};
+use std::path::{Path, PathBuf};
+
+/// 一次表结构升级
+/// - 第i个迁移(从0开始)把数据库从版本i升级到版本i+1
+/// - 版本号记录在`PRAGMA user_version`中, 旧版本程序创建的数据库版本为0
+struct Migration {
+    sql: &'static str,
+}
+
+/// 按顺序排列的所有迁移, 只能在末尾追加, 不能修改已发布的迁移
+const MIGRATIONS: &[Migration] = &[
+    // 初始表结构: mods, profiles, profile_mods
+    Migration {
+        // 使用IF NOT EXISTS, 旧版本程序已建好的表保持不变
+        sql: "
+        CREATE TABLE IF NOT EXISTS mods (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            unique_id TEXT UNIQUE NOT NULL,
+            name TEXT NOT NULL,
+            version TEXT NOT NULL,
+            description TEXT,
+            mod_path TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS profiles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE NOT NULL,
+            description TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS profile_mods (
+            profile_id INTEGER NOT NULL,
+            mod_id INTEGER NOT NULL,
+            PRIMARY KEY (profile_id, mod_id),
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE,
+            FOREIGN KEY (mod_id) REFERENCES mods(id) ON DELETE CASCADE
+        );
+    ",
+    },
+    // 模组的作者和类型, 用户标签: tags, mod_tags
+    Migration {
+        sql: "
+        ALTER TABLE mods ADD COLUMN author TEXT NOT NULL DEFAULT '';
+        ALTER TABLE mods ADD COLUMN entry_dll TEXT;
+        ALTER TABLE mods ADD COLUMN content_pack_for TEXT;
+        CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE NOT NULL
+        );
+        CREATE TABLE mod_tags (
+            mod_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (mod_id, tag_id),
+            FOREIGN KEY (mod_id) REFERENCES mods(id) ON DELETE CASCADE,
+            FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        );
+    ",
+    },
+    // 模组的安装时间
+    Migration {
+        // ADD COLUMN不能使用CURRENT_TIMESTAMP作为默认值, 已有的模组以迁移时间为安装时间
+        sql: "
+        ALTER TABLE mods ADD COLUMN installed_at TIMESTAMP;
+        UPDATE mods SET installed_at = CURRENT_TIMESTAMP;
+    ",
+    },
+    // 模组的笔记和评分: mod_notes
+    Migration {
+        // 以unique_id关联而不是外键, 模组被移除后重新安装时笔记仍在
+        sql: "
+        CREATE TABLE mod_notes (
+            unique_id TEXT PRIMARY KEY,
+            note TEXT NOT NULL DEFAULT '',
+            rating INTEGER CHECK (rating BETWEEN 1 AND 5)
+        );
+    ",
+    },
+    // 模组的依赖
+    Migration {
+        // json数组, 与manifest中Dependencies的格式相同
+        sql: "
+        ALTER TABLE mods ADD COLUMN dependencies TEXT NOT NULL DEFAULT '[]';
+        ALTER TABLE mods ADD COLUMN content_pack_for_version TEXT;
+    ",
+    },
+    // 配置中模组的config.json: profile_mod_configs
+    Migration {
+        // 以unique_id关联模组, 模组重新安装后配置仍在; 配置被删除时一起删除
+        sql: "
+        CREATE TABLE profile_mod_configs (
+            profile_id INTEGER NOT NULL,
+            unique_id TEXT NOT NULL,
+            config TEXT NOT NULL,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (profile_id, unique_id),
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        );
+    ",
+    },
+    // config.json的历史版本: mod_config_history
+    Migration {
+        // profile_id为NULL时是模组文件夹中共享的config.json
+        sql: "
+        CREATE TABLE mod_config_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            profile_id INTEGER,
+            unique_id TEXT NOT NULL,
+            config TEXT NOT NULL,
+            saved_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_mod_config_history ON mod_config_history (unique_id, profile_id);
+    ",
+    },
+    // 存档与配置的绑定: saves
+    Migration {
+        // 存档以文件夹名标识; last_mods是最后一次游玩时配置中模组的unique_id, json数组
+        sql: "
+        CREATE TABLE saves (
+            folder_name TEXT PRIMARY KEY,
+            profile_id INTEGER,
+            last_mods TEXT NOT NULL DEFAULT '[]',
+            last_played_at TIMESTAMP,
+            FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE SET NULL
+        );
+    ",
+    },
+    // 配置独立的存档目录
+    Migration {
+        // 为NULL时使用游戏共享的存档目录
+        sql: "ALTER TABLE profiles ADD COLUMN saves_path TEXT;",
+    },
+    // 模组需要的最低SMAPI和游戏版本
+    Migration {
+        sql: "
+        ALTER TABLE mods ADD COLUMN minimum_api_version TEXT;
+        ALTER TABLE mods ADD COLUMN minimum_game_version TEXT;
+    ",
+    },
+    // 配置所属的游戏安装
+    Migration {
+        // 已有的配置属于默认安装, 与config.rs中的DEFAULT_INSTALLATION一致
+        sql: "ALTER TABLE profiles ADD COLUMN installation TEXT NOT NULL DEFAULT '默认';",
+    },
+    // 配置名只在同一个安装中唯一
+    Migration {
+        // 重建表以去掉name上的UNIQUE, 保留id使其他表的外键仍然有效
+        sql: "
+        CREATE TABLE profiles_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            description TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            saves_path TEXT,
+            installation TEXT NOT NULL DEFAULT '默认',
+            UNIQUE (installation, name)
+        );
+        INSERT INTO profiles_new (id, name, description, created_at, saves_path, installation)
+            SELECT id, name, description, created_at, saves_path, installation FROM profiles;
+        DROP TABLE profiles;
+        ALTER TABLE profiles_new RENAME TO profiles;
+    ",
+    },
+];
+
+/// 程序当前需要的数据库版本
+pub const LATEST_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// 读取数据库的版本
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// 迁移前的备份文件路径, 如`mod_manager.db` -> `mod_manager.v0.bak.db`
+/// # 参数
+/// - `db_path`: db文件的路径
+/// - `version`: 迁移前的版本
+pub fn backup_path(db_path: &Path, version: u32) -> PathBuf {
+    let stem = db_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("mod_manager");
+    db_path.with_file_name(format!("{}.v{}.bak.db", stem, version))
+}
+
+/// 把数据库升级到最新版本
+/// - 每个迁移在单独的事务中执行, 失败时回滚该迁移, 数据库停留在上一个版本
+/// - 已有数据的数据库在迁移前会备份到`backup_path`
+/// - 迁移期间关闭外键约束, 以便迁移中可以重建表
+/// # 参数
+/// - `conn`: 数据库连接
+/// - `db_path`: db文件的路径, 用于备份
+pub fn migrate(conn: &mut Connection, db_path: &Path) -> Result<()> {
+    let version = current_version(conn)?;
+    if version >= LATEST_VERSION {
+        return Ok(());
+    }
+
+    let table_count: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+        [],
+        |row| row.get(0),
+    )?;
+    if table_count > 0 {
+        let backup = backup_path(db_path, version);
+        let _ = std::fs::remove_file(&backup);
+        conn.execute("VACUUM INTO ?1", [backup.to_string_lossy()])?;
+    }
+
+    conn.execute_batch("PRAGMA foreign_keys = OFF;")?;
+    let result = apply_migrations(conn, version);
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    result
+}
+
+fn apply_migrations(conn: &mut Connection, from_version: u32) -> Result<()> {
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(from_version as usize) {
+        let to_version = index as u32 + 1;
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.pragma_update(None, "user_version", to_version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods_manager::mods_info_storage::ModManagerDb;
+    use std::fs;
+
+    /// 旧版本程序(未引入迁移时)创建的数据库, 版本为0
+    fn create_fixture_v0(db_path: &Path) {
+        let conn = Connection::open(db_path).unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE mods (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                unique_id TEXT UNIQUE NOT NULL,
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                description TEXT,
+                mod_path TEXT NOT NULL
+            );
+            CREATE TABLE profiles (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT UNIQUE NOT NULL,
+                description TEXT,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE profile_mods (
+                profile_id INTEGER NOT NULL,
+                mod_id INTEGER NOT NULL,
+                PRIMARY KEY (profile_id, mod_id),
+                FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE,
+                FOREIGN KEY (mod_id) REFERENCES mods(id) ON DELETE CASCADE
+            );
+            INSERT INTO mods (unique_id, name, version, description, mod_path)
+                VALUES ('mod.a', 'A', '1.0.0', 'desc', './a'),
+                       ('mod.b', 'B', '1.0.0', 'desc', './b');
+            INSERT INTO profiles (name, description) VALUES ('p1', 'desc1');
+            INSERT INTO profile_mods (profile_id, mod_id) VALUES (1, 1), (1, 2);
+            ",
+        )
+        .unwrap();
+    }
+
+    fn temp_db_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("mod_manager.db")
+    }
+
+    #[test]
+    fn test_upgrade_from_v0() {
+        let db_path = temp_db_path("smm_test_upgrade_from_v0");
+        create_fixture_v0(&db_path);
+
+        let db = ModManagerDb::new(db_path.clone()).unwrap();
+        assert_eq!(
+            current_version(db.get_connection()).unwrap(),
+            LATEST_VERSION
+        );
+        let mods = db.get_mods().unwrap();
+        assert_eq!(mods.len(), 2);
+        assert!(mods.iter().all(|m| m.installed_at.is_some()));
+        assert_eq!(db.get_mods_from_profile("p1").unwrap().len(), 2);
+
+        // 备份保留了迁移前的数据和版本
+        let backup = Connection::open(backup_path(&db_path, 0)).unwrap();
+        assert_eq!(current_version(&backup).unwrap(), 0);
+        let backup_mods: u32 = backup
+            .query_row("SELECT COUNT(*) FROM mods", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(backup_mods, 2);
+
+        // 再次打开时不需要迁移
+        drop(db);
+        let _ = fs::remove_file(backup_path(&db_path, 0));
+        let db = ModManagerDb::new(db_path.clone()).unwrap();
+        assert_eq!(
+            current_version(db.get_connection()).unwrap(),
+            LATEST_VERSION
+        );
+        assert!(!backup_path(&db_path, 0).exists());
+    }
+
+    #[test]
+    fn test_new_database_is_not_backed_up() {
+        let db_path = temp_db_path("smm_test_new_database");
+        let db = ModManagerDb::new(db_path.clone()).unwrap();
+        assert_eq!(
+            current_version(db.get_connection()).unwrap(),
+            LATEST_VERSION
+        );
+        assert!(!backup_path(&db_path, 0).exists());
+    }
+}
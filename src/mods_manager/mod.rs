@@ -1,27 +1,226 @@
+pub mod compatibility;
+pub mod config_schema;
+pub mod dependency_graph;
+pub mod load_order;
+pub mod migrations;
 pub mod mods_info_storage;
+pub mod mods_query;
 pub mod mods_scanner;
+pub mod mods_watcher;
+#[cfg(test)]
+pub(crate) mod test_support;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
 pub struct ManifestInfo {
     pub Name: String,
     pub Version: String,
     pub Description: String,
     #[serde(alias = "UniqueID")] // 支持 UniqueID 和 UniqueId 两种字段名, 支持不同的manifest文件
     pub UniqueId: String,
+    #[serde(default)]
+    pub Author: String,
+    /// C#模组的dll文件名, 内容包没有这个字段
+    #[serde(default, alias = "EntryDLL")]
+    pub EntryDll: Option<String>,
+    /// 内容包所依赖的框架模组
+    #[serde(default)]
+    pub ContentPackFor: Option<ContentPackFor>,
+    #[serde(default)]
+    pub Dependencies: Vec<ManifestDependency>,
+    /// 需要的最低SMAPI版本
+    #[serde(default)]
+    pub MinimumApiVersion: Option<String>,
+    /// 需要的最低游戏版本
+    #[serde(default)]
+    pub MinimumGameVersion: Option<String>,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct ContentPackFor {
+    #[serde(alias = "UniqueID")]
+    pub UniqueId: String,
+    #[serde(default)]
+    pub MinimumVersion: Option<String>,
+}
+
+/// manifest中`Dependencies`的一项
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ManifestDependency {
+    #[serde(alias = "UniqueID")]
+    pub UniqueId: String,
+    #[serde(default)]
+    pub MinimumVersion: Option<String>,
+    /// SMAPI中默认为true, 为false时是可选依赖
+    #[serde(default = "default_true")]
+    pub IsRequired: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 根据manifest自动得出的模组分类
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ModCategory {
+    /// 含有EntryDll的C#模组
+    CSharp,
+    /// 内容包, `for_mod`是框架模组的UniqueId, 如Content Patcher
+    ContentPack { for_mod: String },
+    /// 两者都没有, 如旧数据库中未重新扫描的模组
+    Unknown,
+}
+
+impl fmt::Display for ModCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModCategory::CSharp => write!(f, "C#模组"),
+            ModCategory::ContentPack { for_mod } => write!(f, "内容包({})", for_mod),
+            ModCategory::Unknown => write!(f, "未知"),
+        }
+    }
+}
+
+impl ManifestInfo {
+    /// 所有依赖, 内容包的框架模组作为第一个必需依赖
+    pub fn all_dependencies(&self) -> Vec<ManifestDependency> {
+        let mut dependencies = Vec::new();
+        if let Some(target) = &self.ContentPackFor {
+            dependencies.push(ManifestDependency {
+                UniqueId: target.UniqueId.clone(),
+                MinimumVersion: target.MinimumVersion.clone(),
+                IsRequired: true,
+            });
+        }
+        dependencies.extend(self.Dependencies.iter().cloned());
+        dependencies
+    }
+
+    pub fn category(&self) -> ModCategory {
+        if let Some(target) = &self.ContentPackFor {
+            ModCategory::ContentPack {
+                for_mod: target.UniqueId.clone(),
+            }
+        } else if self.EntryDll.is_some() {
+            ModCategory::CSharp
+        } else {
+            ModCategory::Unknown
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ModInfo {
     pub manifest_info: ManifestInfo,
     pub path: PathBuf,
+    /// 首次注册进数据库的时间, 扫描得到的模组为None
+    pub installed_at: Option<String>,
+}
+
+/// 模组详情, 见`Manager::mod_details`
+pub struct ModDetails {
+    pub mod_info: ModInfo,
+    /// 磁盘上manifest.json的全部内容, 无法读取时为错误信息
+    pub manifest: std::result::Result<serde_json::Value, String>,
+    /// 模组文件夹中所有文件的大小之和, 单位字节
+    pub disk_size: u64,
+    pub file_count: u64,
+    /// 文件夹中最近修改的文件的修改时间
+    pub last_modified: Option<SystemTime>,
+    pub dependencies: Vec<DependencyStatus>,
+    /// 依赖此模组的已注册模组
+    pub dependents: Vec<Dependent>,
+    /// 包含此模组的配置名
+    pub profiles: Vec<String>,
+}
+
+/// 依赖某个模组的模组, 见`Manager::dependents_of`
+pub struct Dependent {
+    pub mod_info: ModInfo,
+    /// 移除被依赖的模组后, 该模组是否无法加载(直接或间接缺少必需依赖)
+    pub required: bool,
+    /// 包含该模组的配置名
+    pub profiles: Vec<String>,
+}
+
+/// 一项依赖以及它是否已注册
+pub struct DependencyStatus {
+    pub dependency: ManifestDependency,
+    /// 已注册时为该模组的版本
+    pub installed_version: Option<String>,
+}
+
+/// 数据库中一个存档的记录
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveRecord {
+    /// 绑定的配置
+    pub profile_name: Option<String>,
+    /// 最后一次游玩时配置中模组的UniqueId
+    pub last_mods: Vec<String>,
+    pub last_played_at: Option<String>,
+}
+
+/// 可以编辑的config.json
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModConfig {
+    pub value: serde_json::Value,
+    /// 由内容推断出的字段类型, 保存前据此检查
+    pub schema: config_schema::ConfigField,
+    /// content.json无法解析时的错误, 此时字段没有可选值
+    pub schema_error: Option<String>,
+}
+
+/// config.json的一个历史版本
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigVersion {
+    pub id: i64,
+    pub config: String,
+    pub saved_at: String,
+}
+
+/// 用户对一个模组的笔记和评分
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ModNote {
+    pub note: String,
+    /// 1到5分, None为未评分
+    pub rating: Option<u8>,
+}
+
+impl ModNote {
+    pub fn is_empty(&self) -> bool {
+        self.note.trim().is_empty() && self.rating.is_none()
+    }
 }
 
 //Todo: 添加路径属性
+#[derive(Debug, Clone, PartialEq)]
 pub struct Profile {
     pub name: String,
     pub description: String,
     pub create_time: String,
+    /// 配置独立的存档目录, 为None时使用游戏共享的存档
+    pub saves_path: Option<PathBuf>,
+}
+
+/// 已注册但文件夹已不存在的模组
+pub struct OrphanedMod {
+    pub mod_info: ModInfo,
+    /// 仍包含该模组(即含有失效链接)的配置名
+    pub profiles: Vec<String>,
+}
+
+/// 模组库检查的结果
+#[derive(Default)]
+pub struct LibraryHealthReport {
+    pub orphaned_mods: Vec<OrphanedMod>,
+}
+
+impl LibraryHealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.orphaned_mods.is_empty()
+    }
 }
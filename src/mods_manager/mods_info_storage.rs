@@ -1,64 +1,108 @@
-use super::{ManifestInfo, ModInfo, Profile};
-use rusqlite::{Connection, Result};
-use std::path::PathBuf;
+use super::migrations;
+use super::{ConfigVersion, ContentPackFor, ManifestInfo, ModInfo, ModNote, Profile, SaveRecord};
+use crate::config::DEFAULT_INSTALLATION;
+use rusqlite::{Connection, OptionalExtension, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+/// 数据库及其缓存
+/// - 缓存在打开时读取, 之后每个修改数据库的方法都会同步更新受影响的缓存
+/// - 事务回滚时全部重新读取
 pub struct ModManagerDb {
     conn: Connection,
     // 新增缓存, 优化性能
-    cached_mods: Option<Vec<ModInfo>>,
-    cached_profiles: Option<Vec<Profile>>,
+    cached_mods: Vec<ModInfo>,
+    cached_profiles: Vec<Profile>,
+    // 配置名 -> 配置中的模组
+    cached_profile_mods: HashMap<String, Vec<ModInfo>>,
+    // 模组的unique_id -> 按名称排序的标签, 没有标签的模组不在其中
+    cached_mod_tags: HashMap<String, Vec<String>>,
+    // 模组的unique_id -> 笔记和评分
+    cached_mod_notes: HashMap<String, ModNote>,
+    // 当前的游戏安装, 配置的查询和创建限定在该安装中
+    installation: String,
+}
+
+/// 每个模组的config.json保留的历史版本数
+const CONFIG_HISTORY_LIMIT: u32 = 20;
+
+/// 查询模组时使用的列, 与`mod_from_row`的顺序一致
+const MOD_COLUMNS: &str = "m.unique_id, m.name, m.version, m.description, m.mod_path, m.author, m.entry_dll, m.content_pack_for, m.installed_at, m.dependencies, m.content_pack_for_version, m.minimum_api_version, m.minimum_game_version";
+
+fn mod_from_row(row: &rusqlite::Row) -> Result<ModInfo> {
+    let content_pack_for = match row.get::<_, Option<String>>(7)? {
+        Some(unique_id) => Some(ContentPackFor {
+            UniqueId: unique_id,
+            MinimumVersion: row.get(10)?,
+        }),
+        None => None,
+    };
+    let dependencies: String = row.get(9)?;
+    let dependencies = serde_json::from_str(&dependencies).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, e.into())
+    })?;
+    Ok(ModInfo {
+        manifest_info: ManifestInfo {
+            UniqueId: row.get(0)?,
+            Name: row.get(1)?,
+            Version: row.get(2)?,
+            Description: row.get(3)?,
+            Author: row.get(5)?,
+            EntryDll: row.get(6)?,
+            ContentPackFor: content_pack_for,
+            Dependencies: dependencies,
+            MinimumApiVersion: row.get(11)?,
+            MinimumGameVersion: row.get(12)?,
+        },
+        path: PathBuf::from(row.get::<_, String>(4)?),
+        installed_at: row.get(8)?,
+    })
 }
 
-/// Todo: 缓存要同步更新
 impl ModManagerDb {
-    /// 打开或创建数据库连接
+    /// 打开或创建数据库连接, 并把表结构升级到最新版本
     /// # 参数
     /// - `db_path`: db文件的路径
     pub fn new(db_path: PathBuf) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
+        let mut conn = Connection::open(&db_path)?;
+
+        // 创建或升级表结构, 见migrations.rs
+        migrations::migrate(&mut conn, &db_path)?;
 
         // 启用外键约束
         conn.execute("PRAGMA foreign_keys = ON;", [])?;
 
-        // 创建表
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS mods (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                unique_id TEXT UNIQUE NOT NULL,
-                name TEXT NOT NULL,
-                version TEXT NOT NULL,
-                description TEXT,
-                mod_path TEXT NOT NULL
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS profiles (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT UNIQUE NOT NULL,
-                description TEXT,
-                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+        let mut db = ModManagerDb {
+            conn,
+            cached_mods: Vec::new(),
+            cached_profiles: Vec::new(),
+            cached_profile_mods: HashMap::new(),
+            cached_mod_tags: HashMap::new(),
+            cached_mod_notes: HashMap::new(),
+            installation: DEFAULT_INSTALLATION.to_string(),
+        };
+        db.refresh_all_caches()?;
+        Ok(db)
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS profile_mods (
-                profile_id INTEGER NOT NULL,
-                mod_id INTEGER NOT NULL,
-                PRIMARY KEY (profile_id, mod_id),
-                FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE,
-                FOREIGN KEY (mod_id) REFERENCES mods(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+    /// 切换到另一个游戏安装, 之后只能看到该安装的配置
+    /// - 配置名只在同一个安装中唯一, 不同安装可以有同名的配置
+    /// # 参数
+    /// - `installation`: 安装名
+    pub fn set_installation(&mut self, installation: &str) -> Result<()> {
+        self.installation = installation.to_string();
+        self.refresh_all_caches()
+    }
 
-        Ok(ModManagerDb {
-            conn,
-            cached_mods: None,
-            cached_profiles: None,
-        })
+    /// 一个安装中的配置数, 包括当前安装以外的
+    /// # 参数
+    /// - `installation`: 安装名
+    pub fn count_profiles_in_installation(&self, installation: &str) -> Result<u32> {
+        self.conn.query_row(
+            "SELECT COUNT(*) FROM profiles WHERE installation = ?1",
+            [installation],
+            |row| row.get(0),
+        )
     }
 
     pub fn get_connection(&self) -> &Connection {
@@ -66,31 +110,104 @@ impl ModManagerDb {
     }
 
     pub fn get_cached_mods(&self) -> &[ModInfo] {
-        self.cached_mods
-            .as_ref()
+        &self.cached_mods
+    }
+
+    pub fn get_cached_profiles(&self) -> &[Profile] {
+        &self.cached_profiles
+    }
+
+    /// 缓存中一个配置的模组, 配置不存在时为空
+    pub fn get_cached_mods_from_profile(&self, profile_name: &str) -> &[ModInfo] {
+        self.cached_profile_mods
+            .get(profile_name)
             .map(|v| v.as_slice())
             .unwrap_or(&[])
     }
 
-    pub fn get_cached_profiles(&self) -> &[Profile] {
-        self.cached_profiles
-            .as_ref()
+    /// 缓存中一个模组的标签
+    pub fn get_cached_tags_of_mod(&self, mod_unique_id: &str) -> &[String] {
+        self.cached_mod_tags
+            .get(mod_unique_id)
             .map(|v| v.as_slice())
             .unwrap_or(&[])
     }
 
-    fn refresh_cached_mods(&mut self) {
-        self.cached_mods = self.get_mods().unwrap().into();
+    /// 缓存中一个模组的笔记, 没有笔记时为None
+    pub fn get_cached_mod_note(&self, mod_unique_id: &str) -> Option<&ModNote> {
+        self.cached_mod_notes.get(mod_unique_id)
+    }
+
+    fn refresh_cached_mods(&mut self) -> Result<()> {
+        self.cached_mods = self.get_mods()?;
+        Ok(())
+    }
+
+    fn refresh_cached_profiles(&mut self) -> Result<()> {
+        self.cached_profiles = self.get_profiles()?;
+        Ok(())
+    }
+
+    /// 重新读取一个配置的模组, 配置已被删除时移除该项
+    fn refresh_cached_profile_mods(&mut self, profile_name: &str) -> Result<()> {
+        if self.cached_profiles.iter().any(|p| p.name == profile_name) {
+            let mods = self.get_mods_from_profile(profile_name)?;
+            self.cached_profile_mods
+                .insert(profile_name.to_string(), mods);
+        } else {
+            self.cached_profile_mods.remove(profile_name);
+        }
+        Ok(())
+    }
+
+    fn refresh_cached_mod_tags(&mut self) -> Result<()> {
+        self.cached_mod_tags = self.get_mod_tags()?;
+        Ok(())
+    }
+
+    /// 重新读取所有缓存, 需要在`cached_profiles`之后读取各配置的模组
+    fn refresh_all_caches(&mut self) -> Result<()> {
+        self.refresh_cached_mods()?;
+        self.refresh_cached_mod_tags()?;
+        self.cached_mod_notes = self.get_mod_notes()?;
+        self.refresh_cached_profiles()?;
+        self.cached_profile_mods.clear();
+        let names: Vec<String> = self
+            .cached_profiles
+            .iter()
+            .map(|p| p.name.clone())
+            .collect();
+        for name in names {
+            self.refresh_cached_profile_mods(&name)?;
+        }
+        Ok(())
     }
 
-    fn refresh_cached_profiles(&mut self) {
-        self.cached_profiles = self.get_profiles().into();
+    /// 在一个事务中执行`f`
+    /// - `f`返回错误或提交失败时回滚, 并重新读取缓存
+    /// # 参数
+    /// - `f`: 在事务中执行的数据库操作
+    pub fn with_transaction<T, E: From<rusqlite::Error>>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        self.conn.execute_batch("BEGIN")?;
+        let result = f(self).and_then(|v| {
+            self.conn.execute_batch("COMMIT")?;
+            Ok(v)
+        });
+        if result.is_err() {
+            let _ = self.conn.execute_batch("ROLLBACK");
+            self.refresh_all_caches()?;
+        }
+        result
     }
 
     /// 向数据库的mods表插入多个模组, 如果已存在, 则更新信息
+    /// - 安装时间只在首次插入时记录
     /// # 参数
     /// - `mods`:ModInfo的数组
-    pub fn insert_mods(&mut self, mods: &Vec<ModInfo>) {
+    pub fn insert_mods(&mut self, mods: &Vec<ModInfo>) -> Result<()> {
         // 用事务会出现借用, 拼接VALUES子句之后再试
         for mod_info in mods {
             let unique_id = mod_info.manifest_info.UniqueId.clone();
@@ -98,35 +215,70 @@ impl ModManagerDb {
             let version = mod_info.manifest_info.Version.clone();
             let description = mod_info.manifest_info.Description.clone();
             let mod_path = mod_info.path.to_str().unwrap_or("");
+            let author = &mod_info.manifest_info.Author;
+            let entry_dll = &mod_info.manifest_info.EntryDll;
+            let content_pack_for = mod_info
+                .manifest_info
+                .ContentPackFor
+                .as_ref()
+                .map(|c| &c.UniqueId);
+            let content_pack_for_version = mod_info
+                .manifest_info
+                .ContentPackFor
+                .as_ref()
+                .and_then(|c| c.MinimumVersion.as_ref());
+            let dependencies = serde_json::to_string(&mod_info.manifest_info.Dependencies)
+                .unwrap_or_else(|_| "[]".to_string());
 
             //如果unique_id这个UNIQUE属性冲突, 则更新
-            let _ = self.conn.execute(
-                "INSERT INTO mods (unique_id, name, version, description, mod_path) 
-                    VALUES (?1, ?2, ?3, ?4, ?5)
+            self.conn.execute(
+                "INSERT INTO mods (unique_id, name, version, description, mod_path, author, entry_dll, content_pack_for, installed_at, dependencies, content_pack_for_version, minimum_api_version, minimum_game_version) 
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, CURRENT_TIMESTAMP, ?9, ?10, ?11, ?12)
                     ON CONFLICT(unique_id) DO UPDATE SET
                         name = excluded.name,
                         version = excluded.version,
                         description = excluded.description,
-                        mod_path = excluded.mod_path",
-                rusqlite::params![unique_id, name, version, description, mod_path],
-            );
+                        mod_path = excluded.mod_path,
+                        author = excluded.author,
+                        entry_dll = excluded.entry_dll,
+                        content_pack_for = excluded.content_pack_for,
+                        dependencies = excluded.dependencies,
+                        content_pack_for_version = excluded.content_pack_for_version,
+                        minimum_api_version = excluded.minimum_api_version,
+                        minimum_game_version = excluded.minimum_game_version",
+                rusqlite::params![
+                    unique_id,
+                    name,
+                    version,
+                    description,
+                    mod_path,
+                    author,
+                    entry_dll,
+                    content_pack_for,
+                    dependencies,
+                    content_pack_for_version,
+                    mod_info.manifest_info.MinimumApiVersion,
+                    mod_info.manifest_info.MinimumGameVersion
+                ],
+            )?;
         }
 
-        //刷新缓存
-        self.refresh_cached_mods();
+        //刷新缓存, 模组信息的变化也会反映在各配置中
+        self.refresh_all_caches()
     }
 
     /// 从数据库中的mods中删除单个模组, 同时会从所有配置中移除该模组
+    /// - 模组的标签也会一并删除
     /// # 参数
     /// - `mod_unique_id`: 需要删除的模组的uinque_id
-    pub fn remove_mod(&mut self, mod_unique_id: &str) {
-        let _ = self.conn.execute(
+    pub fn remove_mod(&mut self, mod_unique_id: &str) -> Result<()> {
+        self.conn.execute(
             "DELETE FROM mods WHERE unique_id = ?1",
             rusqlite::params![mod_unique_id],
-        );
+        )?;
 
-        //刷新缓存
-        self.refresh_cached_mods();
+        //刷新缓存, 模组会级联地从各配置中移除
+        self.refresh_all_caches()
     }
 
     /// 查询所有模组
@@ -134,21 +286,8 @@ impl ModManagerDb {
     pub fn get_mods(&self) -> Result<Vec<ModInfo>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT id, unique_id, name, version, description, mod_path FROM mods")?;
-        let mod_info_s = stmt.query_map([], |row| {
-            Ok(ModInfo {
-                manifest_info: ManifestInfo {
-                    UniqueId: row.get(1)?,
-                    Name: row.get(2)?,
-                    Version: row.get(3)?,
-                    Description: row.get(4)?,
-                },
-                path: {
-                    let path_str: String = row.get(5)?;
-                    PathBuf::from(path_str)
-                },
-            })
-        })?;
+            .prepare(&format!("SELECT {} FROM mods m ORDER BY m.id", MOD_COLUMNS))?;
+        let mod_info_s = stmt.query_map([], mod_from_row)?;
 
         let mut mod_info_vec = Vec::new();
         for mi in mod_info_s {
@@ -173,18 +312,21 @@ impl ModManagerDb {
     /// # 参数
     /// - `name`: 配置名
     /// - `description`: 配置描述
+    /// # 返回值
+    /// 插入的行数, 同名配置已存在时为0
     pub fn create_profile(
         &mut self,
         name: &str,
         description: &str,
     ) -> Result<usize, rusqlite::Error> {
         let ans = self.conn.execute(
-            "INSERT OR IGNORE INTO profiles (name, description) VALUES (?1, ?2)",
-            rusqlite::params![name, description],
+            "INSERT OR IGNORE INTO profiles (name, description, installation) VALUES (?1, ?2, ?3)",
+            rusqlite::params![name, description, self.installation],
         )?;
 
         //刷新缓存
-        self.refresh_cached_profiles();
+        self.refresh_cached_profiles()?;
+        self.refresh_cached_profile_mods(name)?;
 
         Ok(ans)
     }
@@ -195,47 +337,58 @@ impl ModManagerDb {
     /// # 返回值
     /// 剩余的配置数
     pub fn remove_profile(&mut self, name: &str) -> Result<u16, rusqlite::Error> {
-        let _ = self.conn.execute(
-            "DELETE FROM profiles WHERE name = ?1",
-            rusqlite::params![name],
-        );
+        self.conn.execute(
+            "DELETE FROM profiles WHERE name = ?1 AND installation = ?2",
+            rusqlite::params![name, self.installation],
+        )?;
 
         //刷新缓存
-        self.refresh_cached_profiles();
+        self.refresh_cached_profiles()?;
+        self.refresh_cached_profile_mods(name)?;
 
         self.conn.query_row(
-            "SELECT COUNT(*) FROM profiles",
-            rusqlite::params![],
+            "SELECT COUNT(*) FROM profiles WHERE installation = ?1",
+            rusqlite::params![self.installation],
             |row| row.get(0),
         )
     }
 
-    /// 查询所有配置
+    /// 查询当前安装的所有配置
     /// - 返回值: 配置的数组
-    pub fn get_profiles(&self) -> Vec<Profile> {
-        let mut profiles = Vec::new();
-        let mut stmt = match self
-            .conn
-            .prepare("SELECT name, description, created_at FROM profiles")
-        {
-            Ok(s) => s,
-            Err(_) => return profiles,
-        };
-        let rows = stmt.query_map([], |row| {
+    pub fn get_profiles(&self) -> Result<Vec<Profile>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, description, created_at, saves_path FROM profiles
+                WHERE installation = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([&self.installation], |row| {
             Ok(Profile {
                 name: row.get(0)?,
                 description: row.get(1)?,
                 create_time: row.get(2)?,
+                saves_path: row.get::<_, Option<String>>(3)?.map(PathBuf::from),
             })
-        });
-        if let Ok(rows) = rows {
-            for r in rows {
-                if let Ok(profile) = r {
-                    profiles.push(profile);
-                }
-            }
-        }
-        profiles
+        })?;
+        rows.collect()
+    }
+
+    /// 设置配置独立的存档目录
+    /// # 参数
+    /// - `profile_name`: 配置名
+    /// - `saves_path`: 存档目录, 为None时使用游戏共享的存档
+    pub fn set_profile_saves_path(
+        &mut self,
+        profile_name: &str,
+        saves_path: Option<&Path>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "UPDATE profiles SET saves_path = ?2 WHERE name = ?1 AND installation = ?3",
+            rusqlite::params![
+                profile_name,
+                saves_path.map(|p| p.to_string_lossy()),
+                self.installation
+            ],
+        )?;
+        self.refresh_cached_profiles()
     }
 
     /// 查询一个配置中使用的模组
@@ -243,110 +396,395 @@ impl ModManagerDb {
     /// - `profile_name`: 配置名
     /// # 返回
     /// - 模组信息数组
-    pub fn get_mods_from_profile(&self, profile_name: &str) -> Vec<ModInfo> {
-        let mut mods = Vec::new();
-        let sql = r#"
-            SELECT m.unique_id, m.name, m.version, m.description, m.mod_path
+    pub fn get_mods_from_profile(&self, profile_name: &str) -> Result<Vec<ModInfo>> {
+        let sql = format!(
+            r#"
+            SELECT {}
             FROM mods m
             JOIN profile_mods pm ON m.id = pm.mod_id
             JOIN profiles p ON pm.profile_id = p.id
-            WHERE p.name = ?1
+            WHERE p.name = ?1 AND p.installation = ?2
+            ORDER BY m.id
+        "#,
+            MOD_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([profile_name, &self.installation], mod_from_row)?;
+        rows.collect()
+    }
+
+    /// 查询当前安装中包含某个模组的配置
+    /// # 参数
+    /// - `mod_unique_id`: 模组的unique_id
+    /// # 返回
+    /// - 配置名数组
+    pub fn get_profiles_of_mod(&self, mod_unique_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .get_profiles_of_mod_in_all_installations(mod_unique_id)?
+            .into_iter()
+            .filter(|(installation, _)| *installation == self.installation)
+            .map(|(_, name)| name)
+            .collect())
+    }
+
+    /// 查询所有安装中包含某个模组的配置
+    /// # 参数
+    /// - `mod_unique_id`: 模组的unique_id
+    /// # 返回
+    /// - (安装名, 配置名)数组
+    pub fn get_profiles_of_mod_in_all_installations(
+        &self,
+        mod_unique_id: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let sql = r#"
+            SELECT p.installation, p.name
+            FROM profiles p
+            JOIN profile_mods pm ON p.id = pm.profile_id
+            JOIN mods m ON pm.mod_id = m.id
+            WHERE m.unique_id = ?1
+            ORDER BY p.id
         "#;
-        let mut stmt = match self.conn.prepare(sql) {
-            Ok(s) => s,
-            Err(_) => return mods,
-        };
-        let rows = stmt.query_map([profile_name], |row| {
-            Ok(ModInfo {
-                manifest_info: ManifestInfo {
-                    UniqueId: row.get(0)?,
-                    Name: row.get(1)?,
-                    Version: row.get(2)?,
-                    Description: row.get(3)?,
-                },
-                path: PathBuf::from(row.get::<_, String>(4)?),
-            })
-        });
-        if let Ok(rows) = rows {
-            for r in rows {
-                if let Ok(modinfo) = r {
-                    mods.push(modinfo);
-                }
-            }
-        }
-        mods
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map([mod_unique_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
     }
 
     /// 向一个配置中插入一个模组
+    /// - 已在配置中的模组和未注册的模组会被跳过
     /// # 参数
     /// - `profile_name`: 配置名
     /// - `mods`: 模组信息
-    pub fn insert_mod_to_profile(&self, profile_name: &str, mods: &Vec<ModInfo>) {
-        // 获取 profile_id
-        let profile_id: Option<i64> = self
-            .conn
-            .query_row(
-                "SELECT id FROM profiles WHERE name = ?1",
-                rusqlite::params![profile_name],
-                |row| row.get(0),
-            )
-            .ok();
-        if let Some(profile_id) = profile_id {
-            for m in mods {
-                // 获取 mod_id
-                let mod_id: Option<i64> = self
-                    .conn
-                    .query_row(
-                        "SELECT id FROM mods WHERE unique_id = ?1",
-                        rusqlite::params![m.manifest_info.UniqueId],
-                        |row| row.get(0),
-                    )
-                    .ok();
-                if let Some(mod_id) = mod_id {
-                    let _ = self.conn.execute(
-                        "INSERT INTO profile_mods (profile_id, mod_id) VALUES (?1, ?2)",
-                        rusqlite::params![profile_id, mod_id],
-                    );
-                }
+    pub fn insert_mod_to_profile(&mut self, profile_name: &str, mods: &Vec<ModInfo>) -> Result<()> {
+        // 获取 profile_id, 配置不存在时返回QueryReturnedNoRows
+        let profile_id: i64 = self.conn.query_row(
+            "SELECT id FROM profiles WHERE name = ?1 AND installation = ?2",
+            rusqlite::params![profile_name, self.installation],
+            |row| row.get(0),
+        )?;
+        for m in mods {
+            // 获取 mod_id
+            let mod_id: Option<i64> = self
+                .conn
+                .query_row(
+                    "SELECT id FROM mods WHERE unique_id = ?1",
+                    rusqlite::params![m.manifest_info.UniqueId],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(mod_id) = mod_id {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO profile_mods (profile_id, mod_id) VALUES (?1, ?2)",
+                    rusqlite::params![profile_id, mod_id],
+                )?;
             }
         }
+
+        //刷新缓存
+        self.refresh_cached_profile_mods(profile_name)
     }
 
     /// 从一个配置中移除一个模组
     /// # 参数
     /// - `profile_name`: 配置名
     /// - `mod_info`: 模组信息
-    pub fn remove_mod_from_profile(&self, profile_name: &str, mod_info: ModInfo) {
-        // 获取 profile_id
-        let profile_id: Option<i64> = self
-            .conn
-            .query_row(
-                "SELECT id FROM profiles WHERE name = ?1",
-                rusqlite::params![profile_name],
-                |row| row.get(0),
-            )
-            .ok();
-        // 获取 mod_id
-        let mod_id: Option<i64> = self
-            .conn
-            .query_row(
-                "SELECT id FROM mods WHERE unique_id = ?1",
-                rusqlite::params![mod_info.manifest_info.UniqueId],
-                |row| row.get(0),
-            )
-            .ok();
-        if let (Some(profile_id), Some(mod_id)) = (profile_id, mod_id) {
-            let _ = self.conn.execute(
-                "DELETE FROM profile_mods WHERE profile_id = ?1 AND mod_id = ?2",
-                rusqlite::params![profile_id, mod_id],
-            );
+    pub fn remove_mod_from_profile(&mut self, profile_name: &str, mod_info: ModInfo) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM profile_mods
+                WHERE profile_id = (SELECT id FROM profiles WHERE name = ?1 AND installation = ?3)
+                AND mod_id = (SELECT id FROM mods WHERE unique_id = ?2)",
+            rusqlite::params![
+                profile_name,
+                mod_info.manifest_info.UniqueId,
+                self.installation
+            ],
+        )?;
+
+        //刷新缓存
+        self.refresh_cached_profile_mods(profile_name)
+    }
+
+    /// 给一个模组加上标签, 标签不存在时创建
+    /// - 未注册的模组会被跳过
+    /// # 参数
+    /// - `mod_unique_id`: 模组的unique_id
+    /// - `tag`: 标签名
+    pub fn add_tag_to_mod(&mut self, mod_unique_id: &str, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+            rusqlite::params![tag],
+        )?;
+        self.conn.execute(
+            "INSERT OR IGNORE INTO mod_tags (mod_id, tag_id)
+                SELECT m.id, t.id FROM mods m, tags t
+                WHERE m.unique_id = ?1 AND t.name = ?2",
+            rusqlite::params![mod_unique_id, tag],
+        )?;
+        self.remove_unused_tags()?;
+
+        //刷新缓存
+        self.refresh_cached_mod_tags()
+    }
+
+    /// 移除一个模组的标签, 不再被使用的标签会被删除
+    /// # 参数
+    /// - `mod_unique_id`: 模组的unique_id
+    /// - `tag`: 标签名
+    pub fn remove_tag_from_mod(&mut self, mod_unique_id: &str, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM mod_tags
+                WHERE mod_id = (SELECT id FROM mods WHERE unique_id = ?1)
+                AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+            rusqlite::params![mod_unique_id, tag],
+        )?;
+        self.remove_unused_tags()?;
+
+        //刷新缓存
+        self.refresh_cached_mod_tags()
+    }
+
+    fn remove_unused_tags(&self) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM mod_tags)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// 查询所有模组的标签
+    /// # 返回
+    /// - 模组的unique_id -> 按名称排序的标签
+    pub fn get_mod_tags(&self) -> Result<HashMap<String, Vec<String>>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT m.unique_id, t.name
+                FROM mod_tags mt
+                JOIN mods m ON mt.mod_id = m.id
+                JOIN tags t ON mt.tag_id = t.id
+                ORDER BY t.name",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut mod_tags: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            let (unique_id, tag) = row?;
+            mod_tags.entry(unique_id).or_default().push(tag);
+        }
+        Ok(mod_tags)
+    }
+
+    /// 保存一个模组的笔记和评分, 内容为空时删除记录
+    /// # 参数
+    /// - `mod_unique_id`: 模组的unique_id, 不要求已注册
+    /// - `mod_note`: 笔记和评分
+    pub fn set_mod_note(&mut self, mod_unique_id: &str, mod_note: &ModNote) -> Result<()> {
+        if mod_note.is_empty() {
+            self.conn.execute(
+                "DELETE FROM mod_notes WHERE unique_id = ?1",
+                rusqlite::params![mod_unique_id],
+            )?;
+            self.cached_mod_notes.remove(mod_unique_id);
+        } else {
+            self.conn.execute(
+                "INSERT INTO mod_notes (unique_id, note, rating) VALUES (?1, ?2, ?3)
+                    ON CONFLICT(unique_id) DO UPDATE SET
+                        note = excluded.note,
+                        rating = excluded.rating",
+                rusqlite::params![mod_unique_id, mod_note.note, mod_note.rating],
+            )?;
+            self.cached_mod_notes
+                .insert(mod_unique_id.to_string(), mod_note.clone());
         }
+        Ok(())
+    }
+
+    /// 查询所有模组的笔记和评分
+    pub fn get_mod_notes(&self) -> Result<HashMap<String, ModNote>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT unique_id, note, rating FROM mod_notes")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                ModNote {
+                    note: row.get(1)?,
+                    rating: row.get(2)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// 保存配置中一个模组的config.json, 内容为None时删除记录, 模组使用共享的config.json
+    /// # 参数
+    /// - `profile_name`: 配置名
+    /// - `mod_unique_id`: 模组的unique_id, 不要求已注册
+    /// - `config`: config.json的内容
+    pub fn set_profile_mod_config(
+        &mut self,
+        profile_name: &str,
+        mod_unique_id: &str,
+        config: Option<&str>,
+    ) -> Result<()> {
+        match config {
+            Some(config) => self.conn.execute(
+                "INSERT INTO profile_mod_configs (profile_id, unique_id, config)
+                    SELECT id, ?2, ?3 FROM profiles WHERE name = ?1 AND installation = ?4
+                    ON CONFLICT(profile_id, unique_id) DO UPDATE SET
+                        config = excluded.config,
+                        updated_at = CURRENT_TIMESTAMP",
+                rusqlite::params![profile_name, mod_unique_id, config, self.installation],
+            )?,
+            None => self.conn.execute(
+                "DELETE FROM profile_mod_configs
+                    WHERE profile_id = (SELECT id FROM profiles WHERE name = ?1 AND installation = ?3)
+                    AND unique_id = ?2",
+                rusqlite::params![profile_name, mod_unique_id, self.installation],
+            )?,
+        };
+        Ok(())
+    }
+
+    /// 查询配置中所有模组的config.json
+    /// # 返回值
+    /// 模组的unique_id -> config.json的内容
+    pub fn get_profile_mod_configs(&self, profile_name: &str) -> Result<HashMap<String, String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.unique_id, c.config FROM profile_mod_configs c
+                JOIN profiles p ON c.profile_id = p.id
+                WHERE p.name = ?1 AND p.installation = ?2",
+        )?;
+        let rows = stmt.query_map([profile_name, &self.installation], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect()
+    }
+
+    /// 记录config.json被覆盖前的版本, 每个模组只保留最近`CONFIG_HISTORY_LIMIT`个版本
+    /// # 参数
+    /// - `profile_name`: 配置名, 为None时是模组文件夹中共享的config.json
+    /// - `mod_unique_id`: 模组的unique_id
+    /// - `config`: 被覆盖的内容
+    pub fn add_mod_config_history(
+        &mut self,
+        profile_name: Option<&str>,
+        mod_unique_id: &str,
+        config: &str,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO mod_config_history (profile_id, unique_id, config)
+                VALUES ((SELECT id FROM profiles WHERE name = ?1 AND installation = ?4), ?2, ?3)",
+            rusqlite::params![profile_name, mod_unique_id, config, self.installation],
+        )?;
+        self.conn.execute(
+            "DELETE FROM mod_config_history
+                WHERE unique_id = ?2
+                AND profile_id IS (SELECT id FROM profiles WHERE name = ?1 AND installation = ?4)
+                AND id NOT IN (
+                    SELECT id FROM mod_config_history
+                    WHERE unique_id = ?2
+                    AND profile_id IS (SELECT id FROM profiles WHERE name = ?1 AND installation = ?4)
+                    ORDER BY id DESC LIMIT ?3
+                )",
+            rusqlite::params![
+                profile_name,
+                mod_unique_id,
+                CONFIG_HISTORY_LIMIT,
+                self.installation
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 查询config.json的历史版本, 最新的在前
+    /// # 参数
+    /// - `profile_name`: 配置名, 为None时是模组文件夹中共享的config.json
+    /// - `mod_unique_id`: 模组的unique_id
+    pub fn get_mod_config_history(
+        &self,
+        profile_name: Option<&str>,
+        mod_unique_id: &str,
+    ) -> Result<Vec<ConfigVersion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, config, saved_at FROM mod_config_history
+                WHERE unique_id = ?2
+                AND profile_id IS (SELECT id FROM profiles WHERE name = ?1 AND installation = ?3)
+                ORDER BY id DESC",
+        )?;
+        let params = rusqlite::params![profile_name, mod_unique_id, self.installation];
+        let rows = stmt.query_map(params, |row| {
+            Ok(ConfigVersion {
+                id: row.get(0)?,
+                config: row.get(1)?,
+                saved_at: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// 把存档绑定到配置
+    /// # 参数
+    /// - `folder_name`: 存档文件夹名
+    /// - `profile_name`: 配置名, 为None时解除绑定
+    pub fn bind_save(&mut self, folder_name: &str, profile_name: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO saves (folder_name, profile_id)
+                VALUES (?1, (SELECT id FROM profiles WHERE name = ?2 AND installation = ?3))
+                ON CONFLICT(folder_name) DO UPDATE SET profile_id = excluded.profile_id",
+            rusqlite::params![folder_name, profile_name, self.installation],
+        )?;
+        Ok(())
+    }
+
+    /// 记录存档最后一次游玩时配置中的模组
+    /// # 参数
+    /// - `folder_name`: 存档文件夹名
+    /// - `mod_unique_ids`: 模组的unique_id
+    pub fn record_save_played(
+        &mut self,
+        folder_name: &str,
+        mod_unique_ids: &[String],
+    ) -> Result<()> {
+        let last_mods = serde_json::to_string(mod_unique_ids).unwrap_or_else(|_| "[]".to_string());
+        self.conn.execute(
+            "INSERT INTO saves (folder_name, last_mods, last_played_at)
+                VALUES (?1, ?2, CURRENT_TIMESTAMP)
+                ON CONFLICT(folder_name) DO UPDATE SET
+                    last_mods = excluded.last_mods,
+                    last_played_at = excluded.last_played_at",
+            rusqlite::params![folder_name, last_mods],
+        )?;
+        Ok(())
+    }
+
+    /// 查询所有存档的记录
+    /// - 绑定到其他安装的配置的存档, 配置名为None
+    /// # 返回值
+    /// 存档文件夹名 -> 记录
+    pub fn get_save_records(&self) -> Result<HashMap<String, SaveRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.folder_name, p.name, s.last_mods, s.last_played_at FROM saves s
+                LEFT JOIN profiles p ON s.profile_id = p.id AND p.installation = ?1",
+        )?;
+        let rows = stmt.query_map([&self.installation], |row| {
+            let last_mods: String = row.get(2)?;
+            let last_mods = serde_json::from_str(&last_mods).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+            })?;
+            Ok((
+                row.get(0)?,
+                SaveRecord {
+                    profile_name: row.get(1)?,
+                    last_mods,
+                    last_played_at: row.get(3)?,
+                },
+            ))
+        })?;
+        rows.collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mods_manager::test_support::sample_mod;
 
     use std::fs;
     fn test_db_path() -> PathBuf {
@@ -357,18 +795,6 @@ mod tests {
         let _ = fs::remove_file(test_db_path());
     }
 
-    fn sample_mod(unique_id: &str, name: &str, path: &str) -> ModInfo {
-        ModInfo {
-            manifest_info: ManifestInfo {
-                UniqueId: unique_id.to_string(),
-                Name: name.to_string(),
-                Version: "1.0.0".to_string(),
-                Description: "desc".to_string(),
-            },
-            path: PathBuf::from(path),
-        }
-    }
-
     #[test]
     fn test_full_mods_manager_flow() -> Result<()> {
         clean_db();
@@ -377,38 +803,91 @@ mod tests {
         // 1. 创建配置
         db.create_profile("p1", "desc1")?;
         db.create_profile("p2", "desc2")?;
-        let profiles = db.get_profiles();
+        let profiles = db.get_profiles()?;
         assert_eq!(profiles.len(), 2);
         assert!(profiles.iter().any(|p| p.name == "p1"));
 
         // 2. 插入模组
-        let mods = vec![
-            sample_mod("mod.a", "A", "./a"),
-            sample_mod("mod.b", "B", "./b"),
+        let mut mods = vec![
+            sample_mod("mod.a")
+                .name("A")
+                .version("1.0.0")
+                .description("desc")
+                .path("./a")
+                .build(),
+            sample_mod("mod.b")
+                .name("B")
+                .version("1.0.0")
+                .description("desc")
+                .path("./b")
+                .build(),
         ];
-        db.insert_mods(&mods);
+        mods[0].manifest_info.MinimumApiVersion = Some("4.0.0".to_string());
+        db.insert_mods(&mods)?;
         let all_mods = db.get_mods().unwrap();
         assert!(all_mods.iter().any(|m| m.manifest_info.UniqueId == "mod.a"));
+        assert_eq!(all_mods[0].manifest_info, mods[0].manifest_info);
 
         // 3. 配置关联模组
-        db.insert_mod_to_profile("p1", &mods);
-        let p1_mods = db.get_mods_from_profile("p1");
+        db.insert_mod_to_profile("p1", &mods)?;
+        let p1_mods = db.get_mods_from_profile("p1")?;
         assert_eq!(p1_mods.len(), 2);
+        assert_eq!(db.get_profiles_of_mod("mod.a")?, vec!["p1".to_string()]);
+        assert!(db.get_profiles_of_mod("mod.c")?.is_empty());
 
         // 4. 配置移除模组
-        db.remove_mod_from_profile("p1", mods[0].clone());
-        let p1_mods2 = db.get_mods_from_profile("p1");
+        db.remove_mod_from_profile("p1", mods[0].clone())?;
+        let p1_mods2 = db.get_mods_from_profile("p1")?;
         assert_eq!(p1_mods2.len(), 1);
         assert_eq!(p1_mods2[0].manifest_info.UniqueId, "mod.b");
 
-        // 5. 删除配置
-        db.remove_profile("p2");
-        let profiles2 = db.get_profiles();
+        // 5. 配置中模组的config.json
+        db.set_profile_mod_config("p1", "mod.b", Some("{}"))?;
+        db.set_profile_mod_config("p1", "mod.b", Some("{\"a\": 1}"))?;
+        db.set_profile_mod_config("p2", "mod.a", Some("{}"))?;
+        let p1_configs = db.get_profile_mod_configs("p1")?;
+        assert_eq!(p1_configs.len(), 1);
+        assert_eq!(p1_configs["mod.b"], "{\"a\": 1}");
+
+        // 6. 存档绑定配置, 游玩记录不影响绑定
+        db.bind_save("Farm_1", Some("p2"))?;
+        db.record_save_played("Farm_1", &["mod.a".to_string()])?;
+        let records = db.get_save_records()?;
+        assert_eq!(records["Farm_1"].profile_name.as_deref(), Some("p2"));
+        assert_eq!(records["Farm_1"].last_mods, ["mod.a"]);
+
+        // 7. 配置独立的存档目录
+        db.set_profile_saves_path("p1", Some(Path::new("./p1_saves")))?;
+        assert_eq!(
+            db.get_cached_profiles()[0].saves_path,
+            Some(PathBuf::from("./p1_saves"))
+        );
+        assert_eq!(db.get_profiles()?[1].saves_path, None);
+
+        // 8. 删除配置, 其中的config.json一起删除, 存档解除绑定
+        db.remove_profile("p2")?;
+        assert_eq!(db.get_save_records()?["Farm_1"].profile_name, None);
+        let profiles2 = db.get_profiles()?;
         assert_eq!(profiles2.len(), 1);
         assert_eq!(profiles2[0].name, "p1");
+        let config_count: u32 =
+            db.conn
+                .query_row("SELECT COUNT(*) FROM profile_mod_configs", [], |row| {
+                    row.get(0)
+                })?;
+        assert_eq!(config_count, 1);
+
+        // 9. 标签
+        db.add_tag_to_mod("mod.b", "UI")?;
+        db.add_tag_to_mod("mod.b", "Farm")?;
+        db.add_tag_to_mod("mod.c", "UI")?;
+        assert_eq!(db.get_cached_tags_of_mod("mod.b"), ["Farm", "UI"]);
+        db.remove_tag_from_mod("mod.b", "Farm")?;
+        assert_eq!(db.get_cached_tags_of_mod("mod.b"), ["UI"]);
 
-        // 6. 删除模组
-        db.remove_mod("mod.b");
+        // 10. 删除模组
+        db.remove_mod("mod.b")?;
+        assert!(db.get_cached_tags_of_mod("mod.b").is_empty());
         let all_mods2 = db.get_mods().unwrap();
         assert!(
             all_mods2
@@ -419,9 +898,165 @@ mod tests {
         clean_db();
         Ok(())
     }
+
+    #[test]
+    fn test_installation_scoped_profiles() -> Result<()> {
+        let db_path = std::env::temp_dir().join("smm_test_installations.db");
+        let _ = fs::remove_file(&db_path);
+        let mut db = ModManagerDb::new(db_path.clone())?;
+        db.create_profile("stable", "")?;
+
+        // 另一个安装看不到默认安装的配置, 可以使用相同的配置名
+        db.set_installation("beta")?;
+        assert!(db.get_cached_profiles().is_empty());
+        assert_eq!(db.create_profile("stable", "")?, 1);
+        assert_eq!(db.create_profile("stable", "")?, 0);
+        db.insert_mods(&vec![sample_mod("mod.a").build()])?;
+        db.insert_mod_to_profile("stable", &db.get_mods()?)?;
+        db.create_profile("beta", "")?;
+        assert_eq!(
+            db.get_profiles_of_mod_in_all_installations("mod.a")?,
+            [("beta".to_string(), "stable".to_string())]
+        );
+
+        // 修改和删除只影响当前安装中的同名配置
+        db.set_installation(DEFAULT_INSTALLATION)?;
+        assert!(db.get_cached_mods_from_profile("stable").is_empty());
+        assert!(db.get_profiles_of_mod("mod.a")?.is_empty());
+        db.set_installation("beta")?;
+        assert_eq!(db.remove_profile("stable")?, 1);
+        db.set_installation(DEFAULT_INSTALLATION)?;
+        let names: Vec<_> = db.get_cached_profiles().iter().map(|p| &p.name).collect();
+        assert_eq!(names, ["stable"]);
+        assert_eq!(db.count_profiles_in_installation("beta")?, 1);
+
+        drop(db);
+        let _ = fs::remove_file(&db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_rollback() -> Result<()> {
+        let db_path = PathBuf::from("./test_transaction_rollback.db");
+        let _ = fs::remove_file(&db_path);
+        let mut db = ModManagerDb::new(db_path.clone())?;
+        db.create_profile("p1", "desc1")?;
+
+        let result: Result<()> = db.with_transaction(|db| {
+            db.create_profile("p2", "desc2")?;
+            db.remove_profile("p1")?;
+            Err(rusqlite::Error::QueryReturnedNoRows)
+        });
+        assert!(result.is_err());
+        let names: Vec<_> = db.get_profiles()?.into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["p1".to_string()]);
+        assert_eq!(db.get_cached_profiles().len(), 1);
+
+        drop(db);
+        let _ = fs::remove_file(&db_path);
+        Ok(())
+    }
+
+    /// 缓存与直接查询数据库的结果一致
+    fn assert_cache_coherent(db: &ModManagerDb) -> Result<()> {
+        assert_eq!(db.get_cached_mods(), db.get_mods()?.as_slice());
+        let profiles = db.get_profiles()?;
+        assert_eq!(db.get_cached_profiles(), profiles.as_slice());
+        assert_eq!(db.cached_profile_mods.len(), profiles.len());
+        assert_eq!(db.cached_mod_tags, db.get_mod_tags()?);
+        assert_eq!(db.cached_mod_notes, db.get_mod_notes()?);
+        for p in &profiles {
+            assert_eq!(
+                db.get_cached_mods_from_profile(&p.name),
+                db.get_mods_from_profile(&p.name)?.as_slice()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_coherent_after_random_operations() -> Result<()> {
+        let db_path = PathBuf::from("./test_cache_coherent.db");
+        let _ = fs::remove_file(&db_path);
+        let mut db = ModManagerDb::new(db_path.clone())?;
+
+        // 固定种子的线性同余生成器, 失败时可以复现
+        let mut seed: u64 = 0x5eed;
+        let mut next = |n: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        let profile_names = ["p0", "p1", "p2"];
+        let mod_pool: Vec<ModInfo> = (0..6)
+            .map(|i| {
+                sample_mod(&format!("mod.{}", i))
+                    .name(&format!("M{}", i))
+                    .version("1.0.0")
+                    .description("desc")
+                    .path(&format!("./{}", i))
+                    .build()
+            })
+            .collect();
+
+        for round in 0..300 {
+            let profile = profile_names[next(profile_names.len())];
+            let mut m = mod_pool[next(mod_pool.len())].clone();
+            let tag = ["a", "b"][next(2)];
+            match next(10) {
+                0 => {
+                    db.create_profile(profile, "desc")?;
+                }
+                1 => {
+                    db.remove_profile(profile)?;
+                }
+                2 => {
+                    // 重复注册时会更新模组信息
+                    m.manifest_info.Version = format!("1.0.{}", round);
+                    db.insert_mods(&vec![m])?;
+                }
+                3 => db.remove_mod(&m.manifest_info.UniqueId)?,
+                4 => {
+                    // 配置不存在时返回错误, 缓存不应改变
+                    let _ = db.insert_mod_to_profile(profile, &vec![m]);
+                }
+                5 => db.remove_mod_from_profile(profile, m)?,
+                6 => db.add_tag_to_mod(&m.manifest_info.UniqueId, tag)?,
+                7 => db.remove_tag_from_mod(&m.manifest_info.UniqueId, tag)?,
+                8 => {
+                    // 评分为0时视为清空
+                    let rating = next(6) as u8;
+                    let mod_note = ModNote {
+                        note: if rating.is_multiple_of(2) {
+                            String::new()
+                        } else {
+                            tag.to_string()
+                        },
+                        rating: (rating > 0).then_some(rating),
+                    };
+                    db.set_mod_note(&m.manifest_info.UniqueId, &mod_note)?;
+                }
+                _ => {
+                    let result: Result<()> = db.with_transaction(|db| {
+                        db.create_profile(profile, "desc")?;
+                        db.insert_mod_to_profile(profile, &vec![m])?;
+                        Err(rusqlite::Error::QueryReturnedNoRows)
+                    });
+                    assert!(result.is_err());
+                }
+            }
+            assert_cache_coherent(&db)?;
+        }
+
+        drop(db);
+        let _ = fs::remove_file(&db_path);
+        Ok(())
+    }
 }
 
 // 数据库表设计
+// 以下是初始的表结构, 之后的变化见 migrations.rs
 // 三张表, mods(模组元数据), profiles(记录配置的元信息(不含配置所用的模组)), profile_mods(只记录mods与profiles的多对多关系)
 
 // -- 模组信息表
@@ -0,0 +1,231 @@
+use super::{ModCategory, ModInfo};
+use std::cmp::Ordering;
+
+/// 模组列表的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Version,
+    /// 注册进数据库的时间
+    InstalledAt,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 3] = [SortKey::Name, SortKey::Version, SortKey::InstalledAt];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortKey::Name => "名称",
+            SortKey::Version => "版本",
+            SortKey::InstalledAt => "安装时间",
+        }
+    }
+}
+
+/// 按分类筛选
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CategoryFilter {
+    /// 与分类完全一致, 如某个框架的内容包
+    Exact(ModCategory),
+    /// 所有内容包, 不论框架
+    AnyContentPack,
+}
+
+/// 按模组是否在配置中筛选
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProfileFilter {
+    #[default]
+    All,
+    InProfile(String),
+    NotInAnyProfile,
+}
+
+/// 模组列表的查询条件, 默认不筛选, 按名称升序
+/// - 搜索词和分类只看模组本身, 由`matches`判断
+/// - 标签和配置需要数据库中的信息, 由`Manager::query_mods`判断
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModQuery {
+    /// 以空白分隔的搜索词, 每个词都要匹配
+    pub search: String,
+    pub tag: Option<String>,
+    pub category: Option<CategoryFilter>,
+    pub profile: ProfileFilter,
+    pub sort: SortKey,
+    pub descending: bool,
+}
+
+impl ModQuery {
+    /// 模组是否符合搜索词和分类
+    /// - 名称和UniqueId按子序列模糊匹配, 如`cp`匹配`Content Patcher`
+    /// - 描述, 作者和用户笔记按子串匹配, 它们较长, 子序列几乎总能匹配上
+    /// # 参数
+    /// - `mod_info`: 模组
+    /// - `note`: 用户对该模组的笔记, 没有时为空字符串
+    pub fn matches(&self, mod_info: &ModInfo, note: &str) -> bool {
+        let manifest = &mod_info.manifest_info;
+        let search_ok = self.search.split_whitespace().all(|term| {
+            let term = term.to_lowercase();
+            fuzzy_match(&manifest.Name, &term)
+                || fuzzy_match(&manifest.UniqueId, &term)
+                || manifest.Description.to_lowercase().contains(&term)
+                || manifest.Author.to_lowercase().contains(&term)
+                || note.to_lowercase().contains(&term)
+        });
+        let category_ok = match &self.category {
+            None => true,
+            Some(CategoryFilter::Exact(category)) => manifest.category() == *category,
+            Some(CategoryFilter::AnyContentPack) => {
+                matches!(manifest.category(), ModCategory::ContentPack { .. })
+            }
+        };
+        search_ok && category_ok
+    }
+
+    /// 按查询的排序方式排序, 相同时按UniqueId排序以保持稳定
+    pub fn sort(&self, mods: &mut [&ModInfo]) {
+        mods.sort_by(|a, b| {
+            let (ma, mb) = (&a.manifest_info, &b.manifest_info);
+            let ordering = match self.sort {
+                SortKey::Name => ma.Name.to_lowercase().cmp(&mb.Name.to_lowercase()),
+                SortKey::Version => compare_versions(&ma.Version, &mb.Version),
+                // 时间是"YYYY-MM-DD HH:MM:SS"格式, 可以直接比较字符串
+                SortKey::InstalledAt => a.installed_at.cmp(&b.installed_at),
+            };
+            let ordering = ordering.then_with(|| ma.UniqueId.cmp(&mb.UniqueId));
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+/// `pattern`的字符是否按顺序出现在`text`中, 不区分大小写
+/// # 参数
+/// - `text`: 被搜索的文本
+/// - `pattern`: 小写的搜索词
+pub fn fuzzy_match(text: &str, pattern: &str) -> bool {
+    let mut chars = text.chars().flat_map(char::to_lowercase);
+    pattern.chars().all(|p| chars.any(|c| c == p))
+}
+
+/// 比较两个模组版本号, 如`1.10.0` > `1.9.2`
+/// - 按`.`分段, 数字段按数值比较, 其他按字符串比较, 缺少的段视为0
+/// - 带预发布标记(`-beta`等)的版本低于对应的正式版本
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_core, a_pre) = split_prerelease(a);
+    let (b_core, b_pre) = split_prerelease(b);
+    let a_parts: Vec<&str> = a_core.split('.').collect();
+    let b_parts: Vec<&str> = b_core.split('.').collect();
+    for i in 0..a_parts.len().max(b_parts.len()) {
+        let pa = a_parts.get(i).copied().unwrap_or("0");
+        let pb = b_parts.get(i).copied().unwrap_or("0");
+        let ordering = match (pa.parse::<u64>(), pb.parse::<u64>()) {
+            (Ok(na), Ok(nb)) => na.cmp(&nb),
+            _ => pa.cmp(pb),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(pa), Some(pb)) => pa.cmp(pb),
+    }
+}
+
+fn split_prerelease(version: &str) -> (&str, Option<&str>) {
+    // 去掉构建信息, 如`1.0.0+build.5`
+    let version = version.trim().split('+').next().unwrap_or("");
+    match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods_manager::test_support::sample_mod;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("2.0.0-beta.1", "2.0.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0+build", "1.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_search_and_sort() {
+        let mods = [
+            sample_mod("author.ContentPatcher")
+                .name("Content Patcher")
+                .version("2.0.0")
+                .description("adds more crops")
+                .path("Content Patcher")
+                .installed_at("2024-01-03 00:00:00")
+                .build(),
+            sample_mod("author.LookupAnything")
+                .name("Lookup Anything")
+                .version("1.10.0")
+                .description("adds more crops")
+                .path("Lookup Anything")
+                .installed_at("2024-01-01 00:00:00")
+                .build(),
+            sample_mod("author.ChestsAnywhere")
+                .name("Chests Anywhere")
+                .version("1.9.0")
+                .description("adds more crops")
+                .path("Chests Anywhere")
+                .installed_at("2024-01-02 00:00:00")
+                .build(),
+        ];
+        let query = ModQuery {
+            search: "cp".to_string(),
+            ..Default::default()
+        };
+        let found: Vec<_> = mods.iter().filter(|m| query.matches(m, "")).collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].manifest_info.Name, "Content Patcher");
+
+        // 描述按子串匹配, 所有模组都包含
+        let query = ModQuery {
+            search: "CROPS any".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(mods.iter().filter(|m| query.matches(m, "")).count(), 2);
+
+        // 笔记也参与搜索
+        let query = ModQuery {
+            search: "sve".to_string(),
+            ..Default::default()
+        };
+        assert!(query.matches(&mods[0], "breaks with SVE 1.14"));
+        assert!(!query.matches(&mods[0], ""));
+
+        let mut sorted: Vec<&ModInfo> = mods.iter().collect();
+        let query = ModQuery {
+            sort: SortKey::Version,
+            descending: true,
+            ..Default::default()
+        };
+        query.sort(&mut sorted);
+        let versions: Vec<_> = sorted
+            .iter()
+            .map(|m| m.manifest_info.Version.as_str())
+            .collect();
+        assert_eq!(versions, ["2.0.0", "1.10.0", "1.9.0"]);
+
+        let query = ModQuery {
+            sort: SortKey::InstalledAt,
+            ..Default::default()
+        };
+        query.sort(&mut sorted);
+        assert_eq!(sorted[0].manifest_info.Name, "Lookup Anything");
+    }
+}
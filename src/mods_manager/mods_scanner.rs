@@ -1,12 +1,15 @@
+use rayon::prelude::*;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use walkdir::WalkDir;
 
 use super::{ManifestInfo, ModInfo};
+use crate::error::{self, ManagerError};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 
 pub struct ModScanner {
     mods_folder_path: PathBuf,
@@ -17,6 +20,11 @@ impl ModScanner {
     pub fn set_mods_path(&mut self, mods_path: PathBuf) {
         self.mods_folder_path = mods_path;
     }
+
+    /// 返回当前扫描的mods目录
+    pub fn get_mods_path(&self) -> &PathBuf {
+        &self.mods_folder_path
+    }
 }
 
 impl ModScanner {
@@ -39,29 +47,63 @@ impl ModScanner {
         }
     }
 
-    /// 返回 UniqueId 和 `ModInfo` 的哈希表
-    /// 目前只处理第一层, 后续可以引入walkdir处理多层
-    pub fn scan_mods(&self) -> HashMap<String, ModInfo> {
-        let mut ans = HashMap::new();
-        let entries = WalkDir::new(&self.mods_folder_path).max_depth(2);
-        for entry in entries
+    /// 返回 UniqueId 和 `ModInfo` 的有序表, 以及无法解析的manifest
+    /// - 先按文件名顺序收集mods目录下两层以内的文件夹, 再在线程池中并行解析manifest
+    /// - 多个文件夹的UniqueId相同时, 以遍历顺序中最后一个为准, 结果与线程调度无关
+    /// - manifest无法解析的文件夹不会出现在表中, 其错误按遍历顺序返回
+    pub fn scan_mods(&self) -> (BTreeMap<String, ModInfo>, Vec<ManagerError>) {
+        let folders: Vec<PathBuf> = WalkDir::new(&self.mods_folder_path)
+            .max_depth(2)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(Result::ok)
             .filter(|e| !e.file_type().is_file())
-        {
-            eprintln!("{:?}", entry);
-            let mod_info = self.scan_single_mod(&entry.into_path());
-            let mod_info = match mod_info {
-                Ok(reslut) => reslut.unwrap(),
-                Err(_) => {
-                    continue;
+            .map(|e| e.into_path())
+            .collect();
+
+        // par_iter的collect保持原有顺序
+        let scanned: Vec<error::Result<Option<ModInfo>>> = folders
+            .par_iter()
+            .map(|folder| self.scan_single_mod(folder))
+            .collect();
+
+        let mut ans = BTreeMap::new();
+        let mut errors = Vec::new();
+        for result in scanned {
+            match result {
+                Ok(Some(mod_info)) => {
+                    let unique_id = mod_info.manifest_info.UniqueId.clone();
+                    ans.insert(unique_id, mod_info);
                 }
-            };
-            let unique_id = mod_info.manifest_info.UniqueId.clone();
-            ans.insert(unique_id, mod_info);
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
         }
+        (ans, errors)
+    }
 
-        ans
+    /// 只扫描mods目录下的一个一级文件夹, 用于增量扫描
+    /// - 与`scan_mods`的深度保持一致: 该文件夹本身和它的子文件夹
+    /// # 参数
+    /// - `top_folder`: mods目录下的一级文件夹, 不存在时返回空数组
+    /// # 返回值
+    /// 找到的模组, 以及无法解析的manifest
+    pub fn scan_folder(&self, top_folder: &Path) -> (Vec<ModInfo>, Vec<ManagerError>) {
+        let mut mods = Vec::new();
+        let mut errors = Vec::new();
+        for entry in WalkDir::new(top_folder)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| !e.file_type().is_file())
+        {
+            match self.scan_single_mod(&entry.into_path()) {
+                Ok(Some(mod_info)) => mods.push(mod_info),
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        (mods, errors)
     }
 
     ///从单个模组的manifest.json文件中获取目标信息
@@ -70,46 +112,94 @@ impl ModScanner {
     /// - `mod_folder_name`: 单个模组文件夹路径, 会在本函数中拼接manifest.json文件
     ///
     /// # 返回值
-    /// Result<Option<ModsInfo>, String>, Option中Some是ModsInfo
-    /// 如果该文件夹不是模组文件夹, 返回Err
-    fn scan_single_mod(&self, mod_folder_path: &PathBuf) -> Result<Option<ModInfo>, String> {
+    /// Result<Option<ModsInfo>>, Option中Some是ModsInfo
+    /// 如果该文件夹不是模组文件夹, 返回Ok(None); manifest无法解析时返回Err
+    fn scan_single_mod(&self, mod_folder_path: &PathBuf) -> error::Result<Option<ModInfo>> {
         let manifest_path = mod_folder_path.join(format!("manifest.json"));
 
         //如果不存在, 就不是星露谷模组
         if !manifest_path.exists() {
-            return Err(String::from("Mod does not exist"));
+            return Ok(None);
         }
-
-        // 为了能够正常读取 UTF-8 with BOM 的json文件
-        // 读取文件的原始子节而非字符串
-        let manifest_bytes =
-            fs::read(&manifest_path).map_err(|e| format!("Failed to read manifest: {}", e))?;
-        // 检查是否为UTF8 with BOM, 若是, 则移除UTF-8 BOM
-        let manifest_bytes = if manifest_bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
-            // 移除BOM
-            &manifest_bytes[3..]
-        } else {
-            &manifest_bytes[..]
-        };
-        // 将字节转换为字符串
-        let manifest_content = std::str::from_utf8(manifest_bytes)
-            .map_err(|e| format!("Failed to parse manifest as UTF-8: {}", e))?;
-
-        let manifest: ManifestInfo = match serde_json::from_str(&manifest_content) {
-            Ok(m) => m,
-            Err(e) => return Err(e.to_string()),
-        };
+        let manifest_content = read_manifest_text(&manifest_path)?;
+        let manifest: ManifestInfo =
+            serde_json::from_str(&manifest_content).map_err(|e| ManagerError::Manifest {
+                path: manifest_path.clone(),
+                reason: e.to_string(),
+            })?;
         let mod_info = ModInfo {
             manifest_info: manifest,
             path: manifest_path.parent().unwrap().into(),
+            installed_at: None,
         };
         Ok(Some(mod_info))
     }
 }
 
+/// 读取manifest.json的文本, 支持UTF-8 with BOM
+/// # 参数
+/// - `manifest_path`: manifest.json的路径
+fn read_manifest_text(manifest_path: &Path) -> error::Result<String> {
+    let manifest_error = |reason: String| ManagerError::Manifest {
+        path: manifest_path.to_path_buf(),
+        reason,
+    };
+
+    // 为了能够正常读取 UTF-8 with BOM 的json文件
+    // 读取文件的原始子节而非字符串
+    let manifest_bytes = fs::read(manifest_path)
+        .map_err(|e| manifest_error(format!("Failed to read manifest: {}", e)))?;
+    // 检查是否为UTF8 with BOM, 若是, 则移除UTF-8 BOM
+    let manifest_bytes = if manifest_bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        // 移除BOM
+        &manifest_bytes[3..]
+    } else {
+        &manifest_bytes[..]
+    };
+    // 将字节转换为字符串
+    std::str::from_utf8(manifest_bytes)
+        .map(str::to_string)
+        .map_err(|e| manifest_error(format!("Failed to parse manifest as UTF-8: {}", e)))
+}
+
+/// 读取模组文件夹中完整的manifest.json, 包括`ManifestInfo`以外的字段
+/// # 参数
+/// - `mod_folder_path`: 模组文件夹路径
+pub fn read_manifest_value(mod_folder_path: &Path) -> error::Result<serde_json::Value> {
+    let manifest_path = mod_folder_path.join("manifest.json");
+    let manifest_content = read_manifest_text(&manifest_path)?;
+    serde_json::from_str(&manifest_content).map_err(|e| ManagerError::Manifest {
+        path: manifest_path,
+        reason: e.to_string(),
+    })
+}
+
+/// 统计一个文件夹
+/// # 返回值
+/// (所有文件的大小之和, 文件数, 最近的修改时间)
+pub fn folder_stats(folder: &Path) -> (u64, u64, Option<SystemTime>) {
+    let mut size = 0;
+    let mut count = 0;
+    let mut last_modified = None;
+    for entry in WalkDir::new(folder).into_iter().filter_map(Result::ok) {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_file() {
+            size += metadata.len();
+            count += 1;
+        }
+        if let Ok(modified) = metadata.modified() {
+            last_modified = last_modified.max(Some(modified));
+        }
+    }
+    (size, count, last_modified)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mods_manager::ModCategory;
 
     #[test]
     fn test_json_parse1() {
@@ -161,7 +251,8 @@ mod tests {
     #[test]
     fn test_scan_mods() {
         let mod_scanner = ModScanner::default();
-        let mod_table = mod_scanner.scan_mods();
+        let (mod_table, errors) = mod_scanner.scan_mods();
+        assert!(errors.is_empty());
         assert_eq!(mod_table.len(), 33);
         let g_mod_info = mod_table.get("SilcentHonestFarmer.GoBackHome").unwrap();
         let s_mod_info = mod_table.get("SMAPI.SaveBackup").unwrap();
@@ -178,4 +269,57 @@ mod tests {
         );
         assert_eq!(c_mod_info.manifest_info.Version, "4.3.2");
     }
+
+    #[test]
+    fn test_manifest_category() {
+        let content_pack: ManifestInfo = serde_json::from_str(
+            r#"{"Name": "A", "Version": "1.0.0", "Description": "", "UniqueID": "a.cp",
+                "Author": "me", "ContentPackFor": {"UniqueID": "Pathoschild.ContentPatcher"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            content_pack.category(),
+            ModCategory::ContentPack {
+                for_mod: "Pathoschild.ContentPatcher".to_string()
+            }
+        );
+        let c_sharp: ManifestInfo = serde_json::from_str(
+            r#"{"Name": "B", "Version": "1.0.0", "Description": "", "UniqueID": "b",
+                "EntryDll": "B.dll"}"#,
+        )
+        .unwrap();
+        assert_eq!(c_sharp.category(), ModCategory::CSharp);
+        assert_eq!(c_sharp.Author, "");
+    }
+
+    #[test]
+    fn test_read_manifest_and_folder_stats() {
+        let mod_dir = std::env::temp_dir().join("smm_test_folder_stats");
+        let _ = fs::remove_dir_all(&mod_dir);
+        fs::create_dir_all(mod_dir.join("assets")).unwrap();
+        // 带BOM的manifest, 含ManifestInfo以外的字段
+        let manifest = "\u{FEFF}{\"Name\": \"A\", \"Version\": \"1.0.0\", \"Description\": \"\",
+            \"UniqueID\": \"a\", \"UpdateKeys\": [\"Nexus:1\"],
+            \"Dependencies\": [{\"UniqueID\": \"b\"}, {\"UniqueID\": \"c\", \"IsRequired\": false}]}";
+        fs::write(mod_dir.join("manifest.json"), manifest).unwrap();
+        fs::write(mod_dir.join("assets").join("a.png"), [0u8; 100]).unwrap();
+
+        let value = read_manifest_value(&mod_dir).unwrap();
+        assert_eq!(value["UpdateKeys"][0], "Nexus:1");
+        let mod_info = ModScanner::default()
+            .scan_single_mod(&mod_dir)
+            .unwrap()
+            .unwrap();
+        let dependencies = mod_info.manifest_info.all_dependencies();
+        assert_eq!(dependencies.len(), 2);
+        assert!(dependencies[0].IsRequired);
+        assert!(!dependencies[1].IsRequired);
+
+        let (size, count, last_modified) = folder_stats(&mod_dir);
+        assert_eq!(count, 2);
+        assert_eq!(size, manifest.len() as u64 + 100);
+        assert!(last_modified.is_some());
+
+        let _ = fs::remove_dir_all(&mod_dir);
+    }
 }
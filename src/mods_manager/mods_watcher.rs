@@ -0,0 +1,234 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use super::ModInfo;
+use super::mods_scanner::ModScanner;
+use crate::error::ManagerError;
+
+/// 一次增量扫描得到的模组变化
+#[derive(Default)]
+pub struct ModsChange {
+    pub added: Vec<ModInfo>,
+    pub removed: Vec<ModInfo>,
+    pub changed: Vec<ModInfo>,
+    /// 无法解析的manifest, 这些模组保持原来的状态
+    pub errors: Vec<ManagerError>,
+}
+
+impl ModsChange {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+/// 在后台监视mods目录
+/// - 文件系统事件经过防抖后, 只重新扫描受影响的一级文件夹
+/// - 扫描结果通过通道推送, 由ui在每一帧取出
+/// - drop之后停止监视, 后台线程随之退出
+pub struct ModsWatcher {
+    // 只需要保持其存活
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<ModsChange>,
+}
+
+impl ModsWatcher {
+    /// 开始监视
+    /// # 参数
+    /// - `scanner`: 用于增量扫描, 其mods目录即被监视的目录
+    /// - `debounce`: 最后一个事件之后等待多久才开始扫描
+    pub fn start(scanner: ModScanner, debounce: Duration) -> notify::Result<Self> {
+        let root = scanner.get_mods_path().clone();
+        let (event_tx, event_rx) = mpsc::channel::<Vec<PathBuf>>();
+        let (change_tx, change_rx) = mpsc::channel::<ModsChange>();
+
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                if let Ok(event) = res {
+                    let _ = event_tx.send(event.paths);
+                }
+            })?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+
+        thread::spawn(move || {
+            // 开启监视时ui会完整扫描一次并显示错误, 这里只需要已知的模组
+            let mut known: HashMap<PathBuf, ModInfo> = scanner
+                .scan_mods()
+                .0
+                .into_values()
+                .map(|m| (m.path.clone(), m))
+                .collect();
+
+            // watcher被drop后通道关闭, 线程退出
+            while let Ok(paths) = event_rx.recv() {
+                let mut touched = HashSet::new();
+                collect_top_folders(&root, paths, &mut touched);
+                // 防抖: 直到一段时间内没有新事件才开始扫描
+                loop {
+                    match event_rx.recv_timeout(debounce) {
+                        Ok(paths) => collect_top_folders(&root, paths, &mut touched),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                let change = rescan(&scanner, &mut known, &touched);
+                if !change.is_empty() && change_tx.send(change).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(ModsWatcher {
+            _watcher: watcher,
+            receiver: change_rx,
+        })
+    }
+
+    /// 非阻塞地取出一次模组变化, 没有变化时返回None
+    pub fn try_recv(&self) -> Option<ModsChange> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// 把事件路径归并到mods目录下的一级文件夹
+fn collect_top_folders(root: &Path, paths: Vec<PathBuf>, touched: &mut HashSet<PathBuf>) {
+    for path in paths {
+        if let Some(first) = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|rel| rel.components().next())
+        {
+            touched.insert(root.join(first));
+        }
+    }
+}
+
+/// 重新扫描受影响的一级文件夹, 与已知的模组比较得到变化, 并更新已知模组
+/// - manifest暂时无法解析(如正在写入)的已知模组不视为移除
+fn rescan(
+    scanner: &ModScanner,
+    known: &mut HashMap<PathBuf, ModInfo>,
+    touched: &HashSet<PathBuf>,
+) -> ModsChange {
+    let mut change = ModsChange::default();
+    for top in touched {
+        let old_paths: Vec<PathBuf> = known
+            .keys()
+            .filter(|p| p.starts_with(top))
+            .cloned()
+            .collect();
+        let mut old: HashMap<PathBuf, ModInfo> = old_paths
+            .into_iter()
+            .filter_map(|p| known.remove_entry(&p))
+            .collect();
+
+        let (mods, errors) = scanner.scan_folder(top);
+        for e in &errors {
+            if let ManagerError::Manifest { path, .. } = e
+                && let Some(prev) = path.parent().and_then(|folder| old.remove(folder))
+            {
+                known.insert(prev.path.clone(), prev);
+            }
+        }
+        change.errors.extend(errors);
+        for mod_info in mods {
+            match old.remove(&mod_info.path) {
+                None => change.added.push(mod_info.clone()),
+                Some(prev) if prev.manifest_info != mod_info.manifest_info => {
+                    change.changed.push(mod_info.clone())
+                }
+                Some(_) => {}
+            }
+            known.insert(mod_info.path.clone(), mod_info);
+        }
+        change.removed.extend(old.into_values());
+    }
+
+    // 文件夹改名时, 同一个模组会同时出现在removed和added中, 此时不应视为移除
+    let readded: HashSet<String> = change
+        .added
+        .iter()
+        .map(|m| m.manifest_info.UniqueId.clone())
+        .collect();
+    change
+        .removed
+        .retain(|m| !readded.contains(&m.manifest_info.UniqueId));
+    change
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_manifest(folder: &Path, unique_id: &str, version: &str) {
+        fs::create_dir_all(folder).unwrap();
+        fs::write(
+            folder.join("manifest.json"),
+            format!(
+                r#"{{"Name": "{0}", "Version": "{1}", "Description": "", "UniqueID": "{0}"}}"#,
+                unique_id, version
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rescan_diff() {
+        let root = std::env::temp_dir().join("smm_test_rescan_diff");
+        let _ = fs::remove_dir_all(&root);
+        write_manifest(&root.join("A"), "mod.a", "1.0");
+        write_manifest(&root.join("B"), "mod.b", "1.0");
+        let scanner = ModScanner::from(root.to_str().unwrap());
+        let mut known: HashMap<PathBuf, ModInfo> = scanner
+            .scan_mods()
+            .0
+            .into_values()
+            .map(|m| (m.path.clone(), m))
+            .collect();
+
+        // 新增C, 更新A, 把B改名为B2
+        write_manifest(&root.join("C"), "mod.c", "1.0");
+        write_manifest(&root.join("A"), "mod.a", "2.0");
+        fs::rename(root.join("B"), root.join("B2")).unwrap();
+        let touched: HashSet<PathBuf> =
+            ["A", "B", "B2", "C"].iter().map(|n| root.join(n)).collect();
+        let change = rescan(&scanner, &mut known, &touched);
+
+        let mut added: Vec<_> = change
+            .added
+            .iter()
+            .map(|m| m.manifest_info.UniqueId.as_str())
+            .collect();
+        added.sort();
+        assert_eq!(added, vec!["mod.b", "mod.c"]);
+        assert_eq!(change.changed.len(), 1);
+        assert_eq!(change.changed[0].manifest_info.Version, "2.0");
+        assert!(change.removed.is_empty());
+
+        // 删除C
+        fs::remove_dir_all(root.join("C")).unwrap();
+        let touched: HashSet<PathBuf> = [root.join("C")].into_iter().collect();
+        let change = rescan(&scanner, &mut known, &touched);
+        assert_eq!(change.removed.len(), 1);
+        assert_eq!(change.removed[0].manifest_info.UniqueId, "mod.c");
+        assert_eq!(known.len(), 2);
+
+        // A的manifest写坏了: 报告错误, 但不视为移除
+        fs::write(root.join("A").join("manifest.json"), "{").unwrap();
+        let touched: HashSet<PathBuf> = [root.join("A")].into_iter().collect();
+        let change = rescan(&scanner, &mut known, &touched);
+        assert!(change.removed.is_empty());
+        assert_eq!(change.errors.len(), 1);
+        assert_eq!(known.len(), 2);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
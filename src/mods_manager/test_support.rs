@@ -0,0 +1,76 @@
+use super::{ContentPackFor, ManifestDependency, ManifestInfo, ModInfo};
+use std::path::PathBuf;
+
+/// 测试用的模组, 只填写UniqueId和路径, 其余字段按需设置
+/// - 路径默认为UniqueId
+pub(crate) struct SampleMod(ModInfo);
+
+pub(crate) fn sample_mod(unique_id: &str) -> SampleMod {
+    SampleMod(ModInfo {
+        manifest_info: ManifestInfo {
+            UniqueId: unique_id.to_string(),
+            ..Default::default()
+        },
+        path: PathBuf::from(unique_id),
+        installed_at: None,
+    })
+}
+
+impl SampleMod {
+    pub(crate) fn name(mut self, name: &str) -> Self {
+        self.0.manifest_info.Name = name.to_string();
+        self
+    }
+
+    pub(crate) fn version(mut self, version: &str) -> Self {
+        self.0.manifest_info.Version = version.to_string();
+        self
+    }
+
+    pub(crate) fn description(mut self, description: &str) -> Self {
+        self.0.manifest_info.Description = description.to_string();
+        self
+    }
+
+    pub(crate) fn path(mut self, path: &str) -> Self {
+        self.0.path = PathBuf::from(path);
+        self
+    }
+
+    pub(crate) fn installed_at(mut self, installed_at: &str) -> Self {
+        self.0.installed_at = Some(installed_at.to_string());
+        self
+    }
+
+    /// 加入一项依赖, 不限制最低版本
+    pub(crate) fn dependency(mut self, unique_id: &str, required: bool) -> Self {
+        self.0.manifest_info.Dependencies.push(ManifestDependency {
+            UniqueId: unique_id.to_string(),
+            MinimumVersion: None,
+            IsRequired: required,
+        });
+        self
+    }
+
+    pub(crate) fn content_pack_for(mut self, unique_id: &str) -> Self {
+        self.0.manifest_info.ContentPackFor = Some(ContentPackFor {
+            UniqueId: unique_id.to_string(),
+            MinimumVersion: None,
+        });
+        self
+    }
+
+    pub(crate) fn minimum_api_version(mut self, version: &str) -> Self {
+        self.0.manifest_info.MinimumApiVersion = Some(version.to_string());
+        self
+    }
+
+    pub(crate) fn minimum_game_version(mut self, version: &str) -> Self {
+        self.0.manifest_info.MinimumGameVersion = Some(version.to_string());
+        self
+    }
+
+    pub(crate) fn build(self) -> ModInfo {
+        self.0
+    }
+}
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// 备份的保留策略, 两条规则保留的备份取并集
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// 保留最近的N个备份
+    pub keep_last: usize,
+    /// 最近的N天中(按UTC日期), 每天保留当天最后一个备份
+    pub keep_daily: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            keep_last: 5,
+            keep_daily: 7,
+        }
+    }
+}
+
+/// 一个存档的一次备份
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveBackup {
+    /// 存档文件夹名
+    pub folder_name: String,
+    pub path: PathBuf,
+    pub created_at: SystemTime,
+    /// 存档所在的存档目录, 恢复时写回这里
+    pub saves_path: PathBuf,
+}
+
+/// 一个存档目录的备份存放位置: `root/存档文件夹名/创建时间的毫秒数/`
+/// - 不同的存档目录(游戏的存档目录, 各配置独立的存档目录)使用不同的root, 同名存档互不影响
+pub struct BackupStore {
+    root: PathBuf,
+    saves_path: PathBuf,
+}
+
+impl BackupStore {
+    /// # 参数
+    /// - `root`: 存放备份的目录
+    /// - `saves_path`: 被备份的存档目录
+    pub fn new(root: PathBuf, saves_path: PathBuf) -> Self {
+        BackupStore { root, saves_path }
+    }
+
+    /// 一个备份所在的存放位置
+    pub fn of(backup: &SaveBackup) -> Self {
+        let root = backup
+            .path
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(&backup.path)
+            .to_path_buf();
+        BackupStore::new(root, backup.saves_path.clone())
+    }
+
+    /// 复制存档目录中的一个存档作为新的备份
+    /// # 参数
+    /// - `folder_name`: 存档文件夹名
+    pub fn backup(&self, folder_name: &str) -> io::Result<SaveBackup> {
+        let save_path = self.saves_path.join(folder_name);
+        if !save_path.is_dir() {
+            return Err(io::Error::other(format!("存档{:?}不存在", save_path)));
+        }
+        let folder_name = folder_name.to_string();
+        let mut millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        // 同一毫秒内多次备份时顺延
+        while self
+            .root
+            .join(&folder_name)
+            .join(millis.to_string())
+            .exists()
+        {
+            millis += 1;
+        }
+        let path = self.root.join(&folder_name).join(millis.to_string());
+        // 先复制到临时文件夹, 完整复制后再改名, 中途失败不会留下不完整的备份
+        let partial = path.with_extension("partial");
+        let _ = fs::remove_dir_all(&partial);
+        copy_dir(&save_path, &partial)?;
+        fs::rename(&partial, &path)?;
+        Ok(SaveBackup {
+            folder_name,
+            path,
+            created_at: UNIX_EPOCH + Duration::from_millis(millis),
+            saves_path: self.saves_path.clone(),
+        })
+    }
+
+    /// 一个存档的所有备份, 从新到旧排列
+    /// # 参数
+    /// - `folder_name`: 存档文件夹名
+    pub fn list(&self, folder_name: &str) -> Vec<SaveBackup> {
+        let Ok(entries) = fs::read_dir(self.root.join(folder_name)) else {
+            return Vec::new();
+        };
+        let mut backups: Vec<SaveBackup> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                // 未完成的备份带有扩展名, 无法解析为数字
+                let millis: u64 = entry.file_name().to_str()?.parse().ok()?;
+                Some(SaveBackup {
+                    folder_name: folder_name.to_string(),
+                    path: entry.path(),
+                    created_at: UNIX_EPOCH + Duration::from_millis(millis),
+                    saves_path: self.saves_path.clone(),
+                })
+            })
+            .collect();
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+        backups
+    }
+
+    /// 按保留策略删除一个存档多余的备份
+    /// # 返回值
+    /// 删除的备份数
+    pub fn prune(&self, folder_name: &str, policy: &RetentionPolicy) -> io::Result<usize> {
+        let backups = self.list(folder_name);
+        let mut keep: BTreeSet<usize> = (0..backups.len().min(policy.keep_last)).collect();
+        let mut days = BTreeSet::new();
+        for (i, backup) in backups.iter().enumerate() {
+            let day = backup
+                .created_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                / 86400;
+            // 从新到旧遍历, 每天第一个遇到的就是当天最后一个备份
+            if days.len() < policy.keep_daily && days.insert(day) {
+                keep.insert(i);
+            }
+        }
+        let mut removed = 0;
+        for (i, backup) in backups.iter().enumerate() {
+            if !keep.contains(&i) {
+                fs::remove_dir_all(&backup.path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// 用备份替换存档文件夹
+/// - 写回备份来自的存档目录
+/// - 先复制到临时文件夹, 再与原存档交换, 复制中途失败时原存档不受影响
+/// # 参数
+/// - `backup`: 要恢复的备份
+pub fn restore(backup: &SaveBackup) -> io::Result<()> {
+    let saves_path = &backup.saves_path;
+    let target = saves_path.join(&backup.folder_name);
+    let restoring = saves_path.join(format!(".{}.restoring", backup.folder_name));
+    let old = saves_path.join(format!(".{}.old", backup.folder_name));
+    let _ = fs::remove_dir_all(&restoring);
+    copy_dir(&backup.path, &restoring)?;
+    if target.exists() {
+        let _ = fs::remove_dir_all(&old);
+        fs::rename(&target, &old)?;
+    }
+    fs::rename(&restoring, &target)?;
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    Ok(())
+}
+
+/// 递归复制文件夹
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(from).map_err(io::Error::other)?;
+        let dest = to.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)?;
+        } else {
+            fs::copy(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_prune_and_restore() {
+        let root = PathBuf::from("./test_save_backup");
+        let _ = fs::remove_dir_all(&root);
+        let saves_path = root.join("Saves");
+        let save_path = saves_path.join("Farm_1");
+        fs::create_dir_all(&save_path).unwrap();
+        fs::write(save_path.join("Farm_1"), "day 1").unwrap();
+
+        let store = BackupStore::new(root.join("backups"), saves_path.clone());
+        let first = store.backup("Farm_1").unwrap();
+        fs::write(save_path.join("Farm_1"), "day 2").unwrap();
+        for _ in 0..3 {
+            store.backup("Farm_1").unwrap();
+        }
+        assert_eq!(store.list("Farm_1").len(), 4);
+
+        // 所有备份都在同一天, 保留最近2个
+        let policy = RetentionPolicy {
+            keep_last: 2,
+            keep_daily: 7,
+        };
+        assert_eq!(store.prune("Farm_1", &policy).unwrap(), 2);
+        let backups = store.list("Farm_1");
+        assert_eq!(backups.len(), 2);
+        assert!(!first.path.exists());
+
+        fs::write(save_path.join("Farm_1"), "broken").unwrap();
+        restore(&backups[0]).unwrap();
+        assert_eq!(
+            fs::read_to_string(save_path.join("Farm_1")).unwrap(),
+            "day 2"
+        );
+        assert_eq!(BackupStore::of(&backups[0]).list("Farm_1"), backups);
+
+        // 另一个存档目录中的同名存档使用单独的存放位置, 恢复到自己的目录
+        let profile_saves_path = root.join("profile_saves");
+        fs::create_dir_all(profile_saves_path.join("Farm_1")).unwrap();
+        fs::write(profile_saves_path.join("Farm_1").join("Farm_1"), "sve").unwrap();
+        let profile_store =
+            BackupStore::new(root.join("profile_backups"), profile_saves_path.clone());
+        let profile_backup = profile_store.backup("Farm_1").unwrap();
+        assert_eq!(store.list("Farm_1").len(), 2);
+        fs::write(profile_saves_path.join("Farm_1").join("Farm_1"), "broken").unwrap();
+        restore(&profile_backup).unwrap();
+        assert_eq!(
+            fs::read_to_string(profile_saves_path.join("Farm_1").join("Farm_1")).unwrap(),
+            "sve"
+        );
+        assert_eq!(
+            fs::read_to_string(save_path.join("Farm_1")).unwrap(),
+            "day 2"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 一个存档的基本信息, 来自存档文件夹中的SaveGameInfo
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveInfo {
+    /// 存档文件夹名, 如`Farm_123456789`, 数据库中以此标识存档
+    pub folder_name: String,
+    pub path: PathBuf,
+    pub farmer_name: String,
+    pub farm_name: String,
+    pub year: u32,
+    /// 0到3, 依次为春夏秋冬
+    pub season: u32,
+    pub day: u32,
+    /// SaveGameInfo的修改时间, 即最后一次保存的时间
+    pub last_modified: Option<SystemTime>,
+}
+
+impl SaveInfo {
+    /// 游戏内日期, 如`第1年 春 5日`
+    pub fn date(&self) -> String {
+        let season = match self.season {
+            0 => "春",
+            1 => "夏",
+            2 => "秋",
+            3 => "冬",
+            _ => "?",
+        };
+        format!("第{}年 {} {}日", self.year, season, self.day)
+    }
+}
+
+/// 存档上次游玩时使用的模组不在将要启动的配置中
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveWarning {
+    pub folder_name: String,
+    /// 缺少的模组的UniqueId
+    pub missing_mods: Vec<String>,
+}
+
+impl fmt::Display for SaveWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "存档{}上次游玩时使用的模组不在配置中: {}",
+            self.folder_name,
+            self.missing_mods.join(", ")
+        )
+    }
+}
+
+/// 各系统下星露谷存档的默认位置
+/// - Windows: `%AppData%/StardewValley/Saves`
+/// - Linux和macOS: `~/.config/StardewValley/Saves`
+pub fn default_saves_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        dirs::config_dir()?
+    } else {
+        dirs::home_dir()?.join(".config")
+    };
+    Some(config_dir.join("StardewValley").join("Saves"))
+}
+
+/// 读取存档目录下的所有存档, 按最后保存时间从新到旧排列
+/// - 存档目录不存在时为空
+/// # 参数
+/// - `saves_path`: 存档目录
+/// # 返回值
+/// 存档, 以及无法读取的存档目录和无法解析的存档
+pub fn discover_saves(saves_path: &Path) -> (Vec<SaveInfo>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let entries = match fs::read_dir(saves_path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (Vec::new(), warnings),
+        Err(e) => {
+            warnings.push(format!("无法读取存档目录{:?}: {}", saves_path, e));
+            return (Vec::new(), warnings);
+        }
+    };
+    let mut saves: Vec<SaveInfo> = entries
+        .filter_map(|entry| entry.ok())
+        // 以`.`开头的是恢复备份时的临时文件夹
+        .filter(|entry| {
+            entry.path().is_dir() && !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|entry| {
+            let path = entry.path();
+            let save = read_save(&path);
+            if save.is_none() {
+                warnings.push(format!("跳过无法解析的存档{:?}", path));
+            }
+            save
+        })
+        .collect();
+    saves.sort_by_key(|save| std::cmp::Reverse(save.last_modified));
+    (saves, warnings)
+}
+
+/// 读取一个存档文件夹
+fn read_save(save_path: &Path) -> Option<SaveInfo> {
+    let info_path = save_path.join("SaveGameInfo");
+    let xml = fs::read_to_string(&info_path).ok()?;
+    let number = |tag: &str| xml_tag(&xml, tag)?.parse().ok();
+    Some(SaveInfo {
+        folder_name: save_path.file_name()?.to_string_lossy().to_string(),
+        path: save_path.to_path_buf(),
+        // SaveGameInfo的根元素是玩家, 第一个name就是玩家名
+        farmer_name: xml_tag(&xml, "name")?.to_string(),
+        farm_name: xml_tag(&xml, "farmName").unwrap_or_default().to_string(),
+        year: number("yearForSaveGame")?,
+        season: number("seasonForSaveGame")?,
+        day: number("dayOfMonthForSaveGame")?,
+        last_modified: fs::metadata(&info_path).and_then(|m| m.modified()).ok(),
+    })
+}
+
+/// 取出第一个`<tag>...</tag>`中的文本
+/// - SaveGameInfo由游戏按固定格式写出, 不需要完整的xml解析
+fn xml_tag<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(xml[start..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_save() {
+        let save_path = PathBuf::from("./test_saves/Farm_123456789");
+        let _ = fs::remove_dir_all("./test_saves");
+        fs::create_dir_all(&save_path).unwrap();
+        fs::write(
+            save_path.join("SaveGameInfo"),
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+            <Farmer xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+            <name>Alex</name><farmName>Sunny</farmName>\
+            <yearForSaveGame>2</yearForSaveGame><seasonForSaveGame>1</seasonForSaveGame>\
+            <dayOfMonthForSaveGame>14</dayOfMonthForSaveGame></Farmer>",
+        )
+        .unwrap();
+        // 没有SaveGameInfo的文件夹被跳过, 并给出警告
+        fs::create_dir_all("./test_saves/broken").unwrap();
+
+        let (saves, warnings) = discover_saves(Path::new("./test_saves"));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(saves.len(), 1);
+        assert_eq!(saves[0].folder_name, "Farm_123456789");
+        assert_eq!(saves[0].farmer_name, "Alex");
+        assert_eq!(saves[0].farm_name, "Sunny");
+        assert_eq!(saves[0].date(), "第2年 夏 14日");
+
+        fs::remove_dir_all("./test_saves").unwrap();
+        // 存档目录不存在时没有警告
+        assert_eq!(
+            discover_saves(Path::new("./test_saves")),
+            (Vec::new(), Vec::new())
+        );
+    }
+}
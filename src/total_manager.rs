@@ -1,119 +1,1286 @@
-use crate::link_manager::LinkManager;
+use crate::config::{AppConfig, DEFAULT_INSTALLATION, Installation};
+use crate::error::{ManagerError, Result};
+use crate::export::{DependencyGraphExport, ExportedMod, ProfileExport};
+use crate::game_session::{
+    ConfigSwap, GameProcess, GameSession, LaunchReport, SavesSwap, SessionState,
+};
+use crate::install_detect;
+use crate::journal::{FsStep, Journal};
+use crate::link_manager::{LinkManager, ProfileIntegrityReport};
 use crate::mods_manager;
+use crate::mods_manager::compatibility::{self, InstalledVersions, UnmetRequirement};
+use crate::mods_manager::config_schema;
+use crate::mods_manager::dependency_graph::DependencyGraph;
+use crate::mods_manager::load_order::{self, LoadOrder};
 use crate::mods_manager::mods_info_storage::ModManagerDb;
-use crate::mods_manager::mods_scanner::ModScanner;
+use crate::mods_manager::mods_query::{ModQuery, ProfileFilter};
+use crate::mods_manager::mods_scanner::{self, ModScanner};
+use crate::mods_manager::mods_watcher::{ModsChange, ModsWatcher};
+use crate::save_backup::{self, BackupStore, RetentionPolicy, SaveBackup};
+use crate::saves::{self, SaveInfo, SaveWarning};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::process::Command;
+use std::time::{Duration, SystemTime};
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 //应该让ModInfo和Profile (的成员) 成为通用的统一数据, 这样能使多个接口保持统一
 
 pub struct Manager {
+    /// 数据库, 配置文件等所在的文件夹
+    data_dir: PathBuf,
     smapi_path: PathBuf,
+    /// 星露谷的存档目录
+    saves_path: PathBuf,
+    /// 启动前自动备份存档时的保留策略
+    backup_retention: RetentionPolicy,
+    /// 已安装的SMAPI和游戏版本, 用于检查模组的最低版本要求
+    installed_versions: InstalledVersions,
+    /// 所有游戏安装, 模组扫描, 配置目录和启动都使用`active_installation`
+    installations: Vec<Installation>,
+    active_installation: String,
     scanner: ModScanner,
     database_manager: ModManagerDb,
     link_manager: LinkManager,
+    /// 启动时遇到的问题, 由界面取出后提示
+    warnings: Vec<String>,
 }
 
 impl Manager {
     /// 允许设置scanner的mods路径
     pub fn set_scanner_mods_path(&mut self, mods_path: PathBuf) {
+        if let Some(installation) = self
+            .installations
+            .iter_mut()
+            .find(|i| i.name == self.active_installation)
+        {
+            installation.mods_folder_path = mods_path.to_string_lossy().to_string();
+        }
         self.scanner.set_mods_path(mods_path);
     }
 
+    /// 允许设置存档目录
+    pub fn set_saves_path(&mut self, saves_path: PathBuf) {
+        self.saves_path = saves_path;
+    }
+
+    pub fn get_saves_path(&self) -> &Path {
+        &self.saves_path
+    }
+
+    /// 允许设置存档备份的保留策略
+    pub fn set_backup_retention(&mut self, retention: RetentionPolicy) {
+        self.backup_retention = retention;
+    }
+
+    pub fn get_backup_retention(&self) -> RetentionPolicy {
+        self.backup_retention
+    }
+
+    /// 读取SMAPI和游戏的版本, 日志位于存档目录旁的ErrorLogs中
+    /// # 参数
+    /// - `smapi_path`: SMAPI启动器
+    pub fn detect_installed_versions(&self, smapi_path: &Path) -> InstalledVersions {
+        let log_path = self
+            .saves_path
+            .with_file_name("ErrorLogs")
+            .join("SMAPI-latest.txt");
+        install_detect::read_installed_versions(smapi_path, &log_path)
+    }
+
+    pub fn set_installed_versions(&mut self, versions: InstalledVersions) {
+        self.installed_versions = versions;
+    }
+
+    pub fn get_installed_versions(&self) -> &InstalledVersions {
+        &self.installed_versions
+    }
+
     /// 允许设置SMAPI位置
     pub fn set_smapi_path(&mut self, smapi_path: PathBuf) {
-        self.link_manager.link_parent_path = smapi_path;
+        if let Some(installation) = self
+            .installations
+            .iter_mut()
+            .find(|i| i.name == self.active_installation)
+        {
+            installation.smapi_path = smapi_path.to_string_lossy().to_string();
+        }
+        self.smapi_path = smapi_path;
+    }
+
+    /// 当前安装的配置目录
+    pub fn get_profiles_root(&self) -> &Path {
+        &self.link_manager.link_parent_path
+    }
+
+    /// 更改当前安装的配置目录, 已有的配置文件夹会移动到新目录
+    /// - 新目录必须能创建目录链接
+    /// - 不能与原目录或模组文件夹互相包含
+    /// - 不要在游戏运行时调用
+    /// # 参数
+    /// - `profiles_root`: 新的配置目录
+    /// # 返回值
+    /// 移动的配置数
+    pub fn set_profiles_root(&mut self, profiles_root: &Path) -> Result<usize> {
+        let old_root = self.link_manager.link_parent_path.clone();
+        if profiles_root == old_root {
+            return Ok(0);
+        }
+        let mods_path = self.scanner.get_mods_path().clone();
+        for other in [&old_root, &mods_path] {
+            if profiles_root.starts_with(other) || other.starts_with(profiles_root) {
+                return Err(ManagerError::Config(format!(
+                    "配置目录{:?}不能与{:?}互相包含",
+                    profiles_root, other
+                )));
+            }
+        }
+        if let Some(other) = self
+            .installations
+            .iter()
+            .find(|i| Path::new(&i.profiles_root) == profiles_root)
+        {
+            return Err(ManagerError::Config(format!(
+                "配置目录{:?}已被安装{}使用",
+                profiles_root, other.name
+            )));
+        }
+        let new_link_manager = LinkManager {
+            link_parent_path: profiles_root.to_path_buf(),
+        };
+        new_link_manager.check_link_support()?;
+
+        // 配置文件在最后一步写入, 写入失败时一并回滚
+        let config_path = self.config_path();
+        let mut cfg = AppConfig::load_from_file(&config_path)?;
+        let mut installations = self.installations.clone();
+        let mut active = self.get_active_installation().clone();
+        active.profiles_root = profiles_root.to_string_lossy().to_string();
+        for installation in installations.iter_mut() {
+            if installation.name == active.name {
+                *installation = active.clone();
+            }
+        }
+        cfg.set_installations(installations.clone(), &active);
+
+        let profiles = self.get_all_profiles().to_vec();
+        let moved = self.journaled(|db, link_manager, journal| {
+            let mut moved = 0;
+            for profile in &profiles {
+                let from = old_root.join(&profile.name);
+                let to = profiles_root.join(&profile.name);
+                if to.symlink_metadata().is_ok() {
+                    return Err(ManagerError::Config(format!("{:?}已存在", to)));
+                }
+                // 不在同一个文件系统时无法直接移动, 在新目录中重建链接
+                if from.exists() && fs::rename(&from, &to).is_ok() {
+                    journal.record(FsStep::MovedDir { from, to });
+                } else {
+                    // 重建时只能带走链接, 其他文件需要用户自己处理
+                    if from.exists() {
+                        let real_entries: Vec<PathBuf> = fs::read_dir(&from)?
+                            .filter_map(|entry| entry.ok())
+                            .filter(|entry| entry.file_type().is_ok_and(|t| !t.is_symlink()))
+                            .map(|entry| entry.path())
+                            .collect();
+                        if !real_entries.is_empty() {
+                            return Err(ManagerError::Config(format!(
+                                "配置{}的文件夹中有不是链接的文件, 无法移动到其他磁盘: {:?}",
+                                profile.name, real_entries
+                            )));
+                        }
+                    }
+                    let mod_path_vec = db
+                        .get_cached_mods_from_profile(&profile.name)
+                        .iter()
+                        .map(|mi| mi.path.clone())
+                        .collect();
+                    new_link_manager.create_links(&mod_path_vec, &profile.name, journal)?;
+                    if from.exists() {
+                        link_manager.remove_profile(&profile.name, journal)?;
+                    }
+                }
+                moved += 1;
+            }
+            cfg.save_to_file(&config_path)?;
+            Ok(moved)
+        })?;
+
+        self.installations = installations;
+        self.link_manager = new_link_manager;
+        Ok(moved)
     }
 
     /// 支持重置, 然后重新输入SMAPI与mods的路径
-    pub fn reset(&self) {
+    pub fn reset(&self) -> Result<()> {
         let setting_path = dirs::data_dir()
             .unwrap_or_else(|| std::env::current_dir().unwrap())
             .join("StardewModsManager")
             .join("setting.toml");
 
-        fs::remove_file(setting_path);
+        match fs::remove_file(setting_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
     }
 }
 
 impl Manager {
-    pub fn default() -> Self {
+    /// 同时修改数据库和配置目录的操作
+    /// - 数据库的修改在一个事务中执行, 文件系统的步骤记录在日志中
+    /// - 任何一步失败时, 事务回滚, 已完成的文件系统步骤按相反顺序撤销
+    /// - 撤销失败的步骤附加在返回的错误中; 提交后无法清理的回收目录加入`warnings`
+    /// # 参数
+    /// - `op`: 具体的操作, 文件系统步骤需要记录到传入的日志
+    fn journaled<T>(
+        &mut self,
+        op: impl FnOnce(&mut ModManagerDb, &LinkManager, &mut Journal) -> Result<T>,
+    ) -> Result<T> {
+        let mut journal = Journal::default();
+        let result = self
+            .database_manager
+            .with_transaction(|db| op(db, &self.link_manager, &mut journal));
+        match result {
+            Ok(v) => {
+                self.warnings.extend(journal.commit());
+                Ok(v)
+            }
+            Err(e) => {
+                let failures = journal.rollback(&self.link_manager);
+                if failures.is_empty() {
+                    Err(e)
+                } else {
+                    Err(ManagerError::Config(format!(
+                        "{}; 回滚未完成: {}",
+                        e,
+                        failures.join("; ")
+                    )))
+                }
+            }
+        }
+    }
+
+    /// 打开数据库并读取配置文件
+    /// - 配置文件不存在或无法解析时视为首次使用, 使用默认路径
+    pub fn default() -> Result<Self> {
         // 获取用户数据以及配置文件夹
         let data_dir = dirs::data_dir()
             .unwrap_or_else(|| std::env::current_dir().unwrap())
             .join("StardewModsManager");
         // 若不存在,创建
-        fs::create_dir_all(&data_dir).unwrap_or_else(|e| {
-            eprintln!("无法创建数据目录 {:?}: {}", data_dir, e);
-        });
+        fs::create_dir_all(&data_dir)?;
         let db_path = data_dir.join("mod_manager.db");
         let config_path = data_dir.join("setting.toml");
 
-        // 如果配置文件存在, 说明已经配置模组目录;
-        // 否则为首次使用, 用户可以输入自定义模组目录
-        let scanner = if config_path.exists() {
-            if let Some(cfg) = crate::config::AppConfig::load_from_file(&config_path) {
-                ModScanner::from(&cfg.mods_folder_path)
-            } else {
-                ModScanner::default()
-            }
+        // 如果配置文件存在, 说明已经配置模组目录和SMAPI目录;
+        // 否则为首次使用, 用户可以输入自定义模组目录和SMAPI目录
+        let mut warnings = Vec::new();
+        let config = if config_path.exists() {
+            AppConfig::load_from_file(&config_path)
+                .inspect_err(|e| warnings.push(format!("{}, 使用默认设置", e)))
+                .ok()
         } else {
-            ModScanner::default()
+            None
+        };
+        // 未设置存档目录时使用系统的默认位置
+        let saves_path = config
+            .as_ref()
+            .and_then(|cfg| cfg.saves_path.as_ref().map(PathBuf::from))
+            .or_else(saves::default_saves_path)
+            .unwrap_or_default();
+        let backup_retention = config
+            .as_ref()
+            .map(|cfg| cfg.backup_retention)
+            .unwrap_or_default();
+        let (installations, active) = match config {
+            Some(cfg) => (cfg.installations(), cfg.active_installation()),
+            // 首次使用时优先使用检测到的游戏安装
+            None => {
+                let installation = match install_detect::detect_installs().first() {
+                    Some(install) => {
+                        Installation::from_game_path(DEFAULT_INSTALLATION, &install.game_path)
+                    }
+                    None => Installation::from_game_path(
+                        DEFAULT_INSTALLATION,
+                        Path::new("C:/Program Files (x86)/Steam/steamapps/common/Stardew Valley"),
+                    ),
+                };
+                (vec![installation.clone()], installation)
+            }
         };
 
-        // 如果配置文件存在, 说明已经配置SMAPI目录;
-        // 否则为首次使用, 用户可以输入SMAPI目录
-        let mut smapi_path = PathBuf::from(
-            "C:/Program Files (x86)/Steam/steamapps/common/Stardew Valley/StardewModdingAPI.exe",
-        );
-        if config_path.exists() {
-            if let Some(cfg) = crate::config::AppConfig::load_from_file(&config_path) {
-                smapi_path = PathBuf::from(cfg.smapi_path.clone());
+        let mut database_manager = ModManagerDb::new(db_path)?;
+        database_manager.set_installation(&active.name)?;
+        let mut manager = Manager {
+            data_dir,
+            smapi_path: PathBuf::from(&active.smapi_path),
+            saves_path,
+            backup_retention,
+            installed_versions: InstalledVersions::default(),
+            installations,
+            active_installation: active.name.clone(),
+            scanner: ModScanner::from(&active.mods_folder_path),
+            database_manager,
+            link_manager: LinkManager {
+                link_parent_path: PathBuf::from(&active.profiles_root),
+            },
+            warnings,
+        };
+        if let Err(e) = manager.recover_game_session() {
+            manager.warnings.push(e.to_string());
+        }
+        manager.refresh_installed_versions(&config_path);
+        Ok(manager)
+    }
+
+    /// 重新读取SMAPI和游戏版本, 读取不到的使用配置文件中上次的结果
+    /// - 版本变化时写回配置文件
+    fn refresh_installed_versions(&mut self, config_path: &PathBuf) {
+        let Ok(mut cfg) = AppConfig::load_from_file(config_path) else {
+            self.installed_versions = self.detect_installed_versions(&self.smapi_path);
+            return;
+        };
+        if let Err(e) = cfg.validate() {
+            self.warnings.push(format!("配置文件中的路径有误: {}", e));
+        }
+        let detected = self.detect_installed_versions(&self.smapi_path);
+        let versions = InstalledVersions {
+            smapi: detected.smapi.or(cfg.installed_versions.smapi.clone()),
+            game: detected.game.or(cfg.installed_versions.game.clone()),
+        };
+        if versions != cfg.installed_versions {
+            cfg.installed_versions = versions.clone();
+            if let Err(e) = cfg.save_to_file(config_path) {
+                self.warnings.push(format!("无法保存版本信息: {}", e));
+            }
+        }
+        self.installed_versions = versions;
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.data_dir.join("setting.toml")
+    }
+
+    /// 所有游戏安装
+    pub fn get_installations(&self) -> &[Installation] {
+        &self.installations
+    }
+
+    /// 正在使用的游戏安装
+    pub fn get_active_installation(&self) -> &Installation {
+        self.installations
+            .iter()
+            .find(|i| i.name == self.active_installation)
+            .unwrap_or(&self.installations[0])
+    }
+
+    /// 把所有安装和正在使用的安装写入配置文件
+    fn save_installations(&self) -> Result<()> {
+        let config_path = self.config_path();
+        let mut cfg = AppConfig::load_from_file(&config_path)?;
+        let active = self.get_active_installation().clone();
+        if cfg.active_installation() != active {
+            // 版本属于之前的安装, 切换后重新读取
+            cfg.installed_versions = InstalledVersions::default();
+        }
+        cfg.set_installations(self.installations.clone(), &active);
+        cfg.save_to_file(&config_path)
+    }
+
+    /// 添加一个游戏安装
+    /// # 参数
+    /// - `installation`: 新的安装, 名称不能与已有的重复
+    pub fn add_installation(&mut self, installation: Installation) -> Result<()> {
+        let name = installation.name.trim();
+        if name.is_empty() {
+            return Err(ManagerError::Config("安装名不能为空".to_string()));
+        }
+        if self.installations.iter().any(|i| i.name == name) {
+            return Err(ManagerError::Config(format!("安装{}已存在", name)));
+        }
+        // 不同安装可以有同名配置, 配置目录不能共用
+        if let Some(other) = self
+            .installations
+            .iter()
+            .find(|i| i.profiles_root == installation.profiles_root)
+        {
+            return Err(ManagerError::Config(format!(
+                "配置目录{}已被安装{}使用",
+                installation.profiles_root, other.name
+            )));
+        }
+        self.installations.push(Installation {
+            name: name.to_string(),
+            ..installation
+        });
+        let result = self.save_installations();
+        if result.is_err() {
+            self.installations.pop();
+        }
+        result
+    }
+
+    /// 移除一个游戏安装, 不会删除游戏文件
+    /// - 不能移除正在使用的安装和还有配置的安装
+    /// # 参数
+    /// - `name`: 安装名
+    pub fn remove_installation(&mut self, name: &str) -> Result<()> {
+        if name == self.active_installation {
+            return Err(ManagerError::Config(format!("安装{}正在使用", name)));
+        }
+        let count = self.database_manager.count_profiles_in_installation(name)?;
+        if count > 0 {
+            return Err(ManagerError::Config(format!(
+                "安装{}中还有{}个配置, 请先删除",
+                name, count
+            )));
+        }
+        let before = self.installations.clone();
+        self.installations.retain(|i| i.name != name);
+        let result = self.save_installations();
+        if result.is_err() {
+            self.installations = before;
+        }
+        result
+    }
+
+    /// 切换到另一个游戏安装, 之后扫描该安装的模组文件夹, 只显示该安装的配置
+    /// - 不要在游戏运行时调用
+    /// # 参数
+    /// - `name`: 安装名
+    pub fn switch_installation(&mut self, name: &str) -> Result<()> {
+        let Some(installation) = self.installations.iter().find(|i| i.name == name).cloned() else {
+            return Err(ManagerError::Config(format!("安装{}不存在", name)));
+        };
+        let previous = std::mem::replace(&mut self.active_installation, installation.name.clone());
+        if let Err(e) = self.save_installations() {
+            self.active_installation = previous;
+            return Err(e);
+        }
+        self.database_manager.set_installation(&installation.name)?;
+        self.smapi_path = PathBuf::from(&installation.smapi_path);
+        self.scanner
+            .set_mods_path(PathBuf::from(&installation.mods_folder_path));
+        self.link_manager.link_parent_path = PathBuf::from(&installation.profiles_root);
+        self.refresh_installed_versions(&self.config_path());
+        Ok(())
+    }
+
+    /// 把已登记的模组从当前的模组文件夹移动到独立的模组库, 之后扫描模组库
+    /// - 数据库中的模组路径, 所有安装的配置链接和游戏Mods文件夹中默认配置的链接都会指向新位置
+    /// - 任何一步失败时撤销已移动的模组和修改的链接
+    /// - 未登记的文件夹留在原处
+    /// # 参数
+    /// - `library_path`: 模组库, 不能与当前的模组文件夹互相包含, 需要在同一个磁盘上
+    /// # 返回值
+    /// 移动的模组数
+    pub fn migrate_library(&mut self, library_path: &Path) -> Result<usize> {
+        let old_root = self.scanner.get_mods_path().clone();
+        if library_path.starts_with(&old_root) || old_root.starts_with(library_path) {
+            return Err(ManagerError::Config(format!(
+                "模组库{:?}不能与模组文件夹{:?}互相包含",
+                library_path, old_root
+            )));
+        }
+        fs::create_dir_all(library_path)?;
+        check_same_filesystem(&old_root, library_path)?;
+
+        // 配置文件在最后一步写入, 写入失败时一并回滚
+        let config_path = self.config_path();
+        let mut cfg = AppConfig::load_from_file(&config_path)?;
+        let mut installations = self.installations.clone();
+        for installation in installations.iter_mut() {
+            if installation.name == self.active_installation {
+                installation.mods_folder_path = library_path.to_string_lossy().to_string();
+            }
+        }
+        let active = installations
+            .iter()
+            .find(|i| i.name == self.active_installation)
+            .unwrap_or(&installations[0])
+            .clone();
+        cfg.set_installations(installations.clone(), &active);
+
+        let registered = self.database_manager.get_cached_mods().to_vec();
+        let moved = self.journaled(|db, _, journal| {
+            let mut updated = Vec::new();
+            for mod_info in registered {
+                let Ok(relative) = mod_info.path.strip_prefix(&old_root) else {
+                    continue;
+                };
+                let target = library_path.join(relative);
+                if target.symlink_metadata().is_ok() {
+                    return Err(ManagerError::Config(format!("{:?}已存在", target)));
+                }
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&mod_info.path, &target)?;
+                journal.record(FsStep::MovedDir {
+                    from: mod_info.path.clone(),
+                    to: target.clone(),
+                });
+
+                // 每个安装的配置在各自的配置目录中, 默认配置链接在游戏的Mods文件夹中
+                let unique_id = &mod_info.manifest_info.UniqueId;
+                for (installation_name, profile_name) in
+                    db.get_profiles_of_mod_in_all_installations(unique_id)?
+                {
+                    let Some(installation) =
+                        installations.iter().find(|i| i.name == installation_name)
+                    else {
+                        continue;
+                    };
+                    let link_manager = LinkManager {
+                        link_parent_path: PathBuf::from(&installation.profiles_root),
+                    };
+                    link_manager.relink_mod(&profile_name, &mod_info.path, &target, journal)?;
+                }
+                for installation in &installations {
+                    let link_manager = LinkManager {
+                        link_parent_path: PathBuf::from(&installation.game_path),
+                    };
+                    link_manager.relink_mod("Mods", &mod_info.path, &target, journal)?;
+                }
+
+                updated.push(mods_manager::ModInfo {
+                    path: target,
+                    ..mod_info
+                });
             }
+            db.insert_mods(&updated)?;
+            cfg.save_to_file(&config_path)?;
+            Ok(updated.len())
+        })?;
+
+        self.installations = installations;
+        self.scanner.set_mods_path(library_path.to_path_buf());
+        Ok(moved)
+    }
+
+    /// 当前安装的游戏自带的Mods文件夹
+    pub fn game_mods_path(&self) -> PathBuf {
+        PathBuf::from(&self.get_active_installation().game_path).join("Mods")
+    }
+
+    /// 设置当前安装的默认配置, 其中的模组会链接到游戏的Mods文件夹
+    /// - 不通过管理器启动游戏时, SMAPI加载的就是默认配置
+    /// - 模组库还在游戏的Mods文件夹中时不能设置
+    /// # 参数
+    /// - `profile_name`: 配置名, None表示取消默认配置并移除游戏Mods文件夹中的链接
+    pub fn set_default_profile(&mut self, profile_name: Option<&str>) -> Result<()> {
+        let game_mods_path = self.game_mods_path();
+        if profile_name.is_some() && self.scanner.get_mods_path().starts_with(&game_mods_path) {
+            return Err(ManagerError::Config(
+                "模组库在游戏的Mods文件夹中, 请先迁移模组库".to_string(),
+            ));
+        }
+        let before = self.installations.clone();
+        if let Some(installation) = self
+            .installations
+            .iter_mut()
+            .find(|i| i.name == self.active_installation)
+        {
+            installation.default_profile = profile_name.map(str::to_string);
+        }
+        let result = self
+            .materialize_default_profile()
+            .and_then(|_| self.save_installations());
+        if result.is_err() {
+            self.installations = before;
+        }
+        result
+    }
+
+    /// 让游戏Mods文件夹中的链接与默认配置一致, 没有默认配置时移除所有链接
+    /// - 被同名文件夹占用而没有链接的模组加入`warnings`
+    fn materialize_default_profile(&mut self) -> Result<()> {
+        let game_mods_path = self.game_mods_path();
+        if self.scanner.get_mods_path().starts_with(&game_mods_path) {
+            return Ok(());
+        }
+        let mod_path_vec: Vec<PathBuf> = match &self.get_active_installation().default_profile {
+            Some(name) => self
+                .get_mods_from_profile(name)
+                .iter()
+                .map(|mi| mi.path.clone())
+                .collect(),
+            None if game_mods_path.exists() => Vec::new(),
+            None => return Ok(()),
+        };
+        let skipped = self
+            .link_manager
+            .mirror_links(&game_mods_path, &mod_path_vec)?;
+        for mod_path in skipped {
+            self.warnings.push(format!(
+                "游戏的Mods文件夹中已有同名的文件夹, 默认配置中的{:?}没有链接",
+                mod_path
+            ));
         }
+        Ok(())
+    }
+
+    /// 修改的配置是默认配置时, 同步游戏Mods文件夹中的链接
+    fn sync_default_profile(&mut self, profile_name: &str) -> Result<()> {
+        if self.get_active_installation().default_profile.as_deref() == Some(profile_name) {
+            self.materialize_default_profile()?;
+        }
+        Ok(())
+    }
+
+    /// 取出启动时遇到的问题, 取出后清空
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
 
-        Manager {
-            smapi_path,
-            scanner,
-            database_manager: ModManagerDb::new(db_path).unwrap(),
-            link_manager: LinkManager::default(),
+    /// 上次游戏运行时管理器没有正常结束, 取回配置的修改并恢复被替换的文件
+    /// - 上次启动的游戏仍在运行时不做修改, 留到下次打开管理器或启动游戏时
+    fn recover_game_session(&mut self) -> Result<()> {
+        let state = SessionState::load(&self.session_path())
+            .map_err(|e| ManagerError::Config(format!("无法读取上次的运行记录: {}", e)))?;
+        let Some(state) = state else {
+            return Ok(());
+        };
+        if state.process.as_ref().is_some_and(GameProcess::is_running) {
+            return Err(ManagerError::Config(format!(
+                "配置{}上次启动的游戏仍在运行, 游戏退出后才能恢复替换的config.json和存档",
+                state.profile_name
+            )));
         }
+        let profile_name = state.profile_name.clone();
+        self.finish_game_session(state).map_err(|e| {
+            ManagerError::Config(format!(
+                "无法恢复配置{}上次未结束的运行: {}",
+                profile_name, e
+            ))
+        })?;
+        Ok(())
     }
 
     /// 本地所有的模组注册进入数据库
     /// - 如果模组已存在, 则更新模组信息
-    pub fn register_all_mods(&mut self) {
-        let all_mods = self.scanner.scan_mods();
+    /// # 返回值
+    /// manifest无法解析的模组, 它们不会被注册
+    pub fn register_all_mods(&mut self) -> Result<Vec<ManagerError>> {
+        let (all_mods, errors) = self.scanner.scan_mods();
         let all_mods: Vec<mods_manager::ModInfo> = all_mods.into_values().collect();
-        self.database_manager.insert_mods(&all_mods);
+        self.database_manager.insert_mods(&all_mods)?;
+        Ok(errors)
     }
 
     /// 移除一个模组, 实际上这个模组文件夹不被删除, 但是指向它的链接需要删除
+    /// - 先删除所有配置中指向它的链接, 再删除数据库记录(profile_mods随之级联删除)
     /// # 参数
     /// - `mod_unique_id` 模组的UniqueId
-    ///
-    /// Todo: 该函数的移除链接应该交给底层实现
-    pub fn remove_mod(&mut self, mod_unique_id: &str) {}
+    pub fn remove_mod(&mut self, mod_unique_id: &str) -> Result<()> {
+        let Some(mod_info) = self
+            .get_registered_mods()
+            .iter()
+            .find(|m| m.manifest_info.UniqueId == mod_unique_id)
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        // 每个安装的配置在各自的配置目录中
+        let profiles_roots: HashMap<String, PathBuf> = self
+            .installations
+            .iter()
+            .map(|i| (i.name.clone(), PathBuf::from(&i.profiles_root)))
+            .collect();
+        self.journaled(|db, _, journal| {
+            for (installation, profile_name) in
+                db.get_profiles_of_mod_in_all_installations(mod_unique_id)?
+            {
+                let Some(profiles_root) = profiles_roots.get(&installation) else {
+                    continue;
+                };
+                let link_manager = LinkManager {
+                    link_parent_path: profiles_root.clone(),
+                };
+                link_manager.remove_mod_from_profile(
+                    &profile_name,
+                    mod_info.path.clone(),
+                    journal,
+                )?;
+            }
+            db.remove_mod(mod_unique_id)?;
+            Ok(())
+        })
+    }
+
+    /// 模组库检查: 找出文件夹已被删除的已注册模组, 以及受影响的配置
+    pub fn check_library_health(&self) -> Result<mods_manager::LibraryHealthReport> {
+        let mut orphaned_mods = Vec::new();
+        for m in self
+            .get_registered_mods()
+            .iter()
+            .filter(|m| !m.path.exists())
+        {
+            orphaned_mods.push(mods_manager::OrphanedMod {
+                mod_info: m.clone(),
+                profiles: self
+                    .database_manager
+                    .get_profiles_of_mod(&m.manifest_info.UniqueId)?,
+            });
+        }
+        Ok(mods_manager::LibraryHealthReport { orphaned_mods })
+    }
+
+    /// 一次性清理检查结果中的模组: 删除数据库记录和所有配置中的失效链接
+    /// - 检查之后文件夹又恢复了的模组不会被清理
+    /// # 返回值
+    /// 被清理的模组数量
+    pub fn purge_orphaned_mods(
+        &mut self,
+        report: &mods_manager::LibraryHealthReport,
+    ) -> Result<usize> {
+        let mut purged = 0;
+        for orphan in &report.orphaned_mods {
+            if orphan.mod_info.path.exists() {
+                continue;
+            }
+            self.remove_mod(&orphan.mod_info.manifest_info.UniqueId)?;
+            purged += 1;
+        }
+        Ok(purged)
+    }
+
+    /// 在后台监视mods目录, 模组变化会通过返回的监视器推送
+    /// - drop监视器即停止监视
+    pub fn watch_mods_folder(&self) -> Result<ModsWatcher> {
+        Ok(ModsWatcher::start(
+            self.scanner.clone(),
+            Duration::from_millis(800),
+        )?)
+    }
+
+    /// 把监视器推送的模组变化同步到数据库
+    /// - 被删除的模组保留记录和链接, 文件夹可能只是暂时消失(重命名, 解压覆盖),
+    ///   由模组库检查`check_library_health`列出, 用户确认后再清理
+    /// # 参数
+    /// - `change`: 一次增量扫描得到的模组变化
+    pub fn apply_mods_change(&mut self, change: &ModsChange) -> Result<()> {
+        let updated: Vec<mods_manager::ModInfo> = change
+            .added
+            .iter()
+            .chain(change.changed.iter())
+            .cloned()
+            .collect();
+        if !updated.is_empty() {
+            self.database_manager.insert_mods(&updated)?;
+        }
+        Ok(())
+    }
 
     /// 返回所有模组的信息
     pub fn get_registered_mods(&self) -> &[mods_manager::ModInfo] {
         self.database_manager.get_cached_mods()
     }
 
+    /// 已注册的模组中, 最低SMAPI或游戏版本高于已安装版本的
+    pub fn unmet_version_requirements(&self) -> Vec<UnmetRequirement> {
+        compatibility::unmet_requirements(self.get_registered_mods(), &self.installed_versions)
+    }
+
+    /// 给多个模组加上同一个标签
+    /// # 参数
+    /// - `mod_unique_ids`: 模组的UniqueId
+    /// - `tag`: 标签名, 首尾空白会被去掉
+    pub fn tag_mods(&mut self, mod_unique_ids: &[String], tag: &str) -> Result<()> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(ManagerError::Config("标签名不能为空".to_string()));
+        }
+        self.database_manager.with_transaction(|db| {
+            for unique_id in mod_unique_ids {
+                db.add_tag_to_mod(unique_id, tag)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// 移除一个模组的标签
+    pub fn untag_mod(&mut self, mod_unique_id: &str, tag: &str) -> Result<()> {
+        Ok(self
+            .database_manager
+            .remove_tag_from_mod(mod_unique_id, tag)?)
+    }
+
+    /// 返回一个模组的标签
+    pub fn get_tags_of_mod(&self, mod_unique_id: &str) -> &[String] {
+        self.database_manager.get_cached_tags_of_mod(mod_unique_id)
+    }
+
+    /// 返回所有用到的标签, 按名称排序
+    pub fn get_all_tags(&self) -> Vec<String> {
+        let tags: std::collections::BTreeSet<&String> = self
+            .get_registered_mods()
+            .iter()
+            .flat_map(|m| self.get_tags_of_mod(&m.manifest_info.UniqueId))
+            .collect();
+        tags.into_iter().cloned().collect()
+    }
+
+    /// 返回已注册模组中出现的所有分类
+    pub fn get_all_categories(&self) -> Vec<mods_manager::ModCategory> {
+        let categories: std::collections::BTreeSet<_> = self
+            .get_registered_mods()
+            .iter()
+            .map(|m| m.manifest_info.category())
+            .collect();
+        categories.into_iter().collect()
+    }
+
+    /// 按查询条件筛选并排序已注册的模组
+    /// # 参数
+    /// - `query`: 查询条件, 见`ModQuery`
+    pub fn query_mods(&self, query: &ModQuery) -> Vec<&mods_manager::ModInfo> {
+        // 在任意配置中的模组
+        let in_any_profile: HashSet<&str> = match query.profile {
+            ProfileFilter::NotInAnyProfile => self
+                .get_all_profiles()
+                .iter()
+                .flat_map(|p| self.get_mods_from_profile(&p.name))
+                .map(|m| m.manifest_info.UniqueId.as_str())
+                .collect(),
+            _ => HashSet::new(),
+        };
+        let in_profile: HashSet<&str> = match &query.profile {
+            ProfileFilter::InProfile(name) => self
+                .get_mods_from_profile(name)
+                .iter()
+                .map(|m| m.manifest_info.UniqueId.as_str())
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        let mut mods: Vec<&mods_manager::ModInfo> = self
+            .get_registered_mods()
+            .iter()
+            .filter(|m| {
+                let unique_id = m.manifest_info.UniqueId.as_str();
+                let tag_ok = query
+                    .tag
+                    .as_ref()
+                    .is_none_or(|tag| self.get_tags_of_mod(unique_id).contains(tag));
+                let profile_ok = match query.profile {
+                    ProfileFilter::All => true,
+                    ProfileFilter::InProfile(_) => in_profile.contains(unique_id),
+                    ProfileFilter::NotInAnyProfile => !in_any_profile.contains(unique_id),
+                };
+                let note = self
+                    .database_manager
+                    .get_cached_mod_note(unique_id)
+                    .map_or("", |n| n.note.as_str());
+                tag_ok && profile_ok && query.matches(m, note)
+            })
+            .collect();
+        query.sort(&mut mods);
+        mods
+    }
+
+    /// 查看一个模组的详细信息
+    /// - 会读取磁盘上的manifest并遍历模组文件夹, 不适合每一帧调用
+    /// # 参数
+    /// - `mod_unique_id`: 模组的UniqueId
+    pub fn mod_details(&self, mod_unique_id: &str) -> Result<mods_manager::ModDetails> {
+        let registered = self.get_registered_mods();
+        let mod_info = self.registered_mod(mod_unique_id)?;
+
+        // 文件夹已被删除时仍然显示数据库中的信息
+        let manifest = mods_scanner::read_manifest_value(&mod_info.path).map_err(|e| e.to_string());
+        let (disk_size, file_count, last_modified) = mods_scanner::folder_stats(&mod_info.path);
+
+        // SMAPI中UniqueId不区分大小写
+        let dependencies = mod_info
+            .manifest_info
+            .all_dependencies()
+            .into_iter()
+            .map(|dependency| {
+                let installed_version = registered
+                    .iter()
+                    .find(|m| {
+                        m.manifest_info
+                            .UniqueId
+                            .eq_ignore_ascii_case(&dependency.UniqueId)
+                    })
+                    .map(|m| m.manifest_info.Version.clone());
+                mods_manager::DependencyStatus {
+                    dependency,
+                    installed_version,
+                }
+            })
+            .collect();
+        let dependents = self.dependents_of(mod_unique_id);
+        let profiles = self.profiles_containing(mod_unique_id);
+
+        Ok(mods_manager::ModDetails {
+            mod_info: mod_info.clone(),
+            manifest,
+            disk_size,
+            file_count,
+            last_modified,
+            dependencies,
+            dependents,
+            profiles,
+        })
+    }
+
+    /// 包含某个模组的配置名
+    fn profiles_containing(&self, mod_unique_id: &str) -> Vec<String> {
+        self.get_all_profiles()
+            .iter()
+            .filter(|p| {
+                self.get_mods_from_profile(&p.name)
+                    .iter()
+                    .any(|m| m.manifest_info.UniqueId == mod_unique_id)
+            })
+            .map(|p| p.name.clone())
+            .collect()
+    }
+
+    /// 根据已注册模组的manifest建立依赖图
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::build(self.get_registered_mods())
+    }
+
+    /// 依赖某个模组的已注册模组, 以及包含它们的配置
+    /// - 包括直接依赖它的模组, 和移除它后会间接缺少必需依赖的模组
+    /// # 参数
+    /// - `mod_unique_id`: 被依赖的模组的UniqueId
+    pub fn dependents_of(&self, mod_unique_id: &str) -> Vec<mods_manager::Dependent> {
+        let graph = self.dependency_graph();
+        let mut dependents: BTreeMap<String, bool> =
+            graph.dependents_of(mod_unique_id).into_iter().collect();
+        for unique_id in graph.broken_by_removal(&[mod_unique_id], None) {
+            dependents.insert(unique_id, true);
+        }
+        dependents
+            .into_iter()
+            .filter_map(|(unique_id, required)| {
+                let mod_info = self
+                    .get_registered_mods()
+                    .iter()
+                    .find(|m| m.manifest_info.UniqueId == unique_id)?;
+                Some(mods_manager::Dependent {
+                    mod_info: mod_info.clone(),
+                    required,
+                    profiles: self.profiles_containing(&unique_id),
+                })
+            })
+            .collect()
+    }
+
+    /// 从配置中移除一个模组后, 配置中因缺少必需依赖而无法加载的模组
+    /// - 界面在调用`remove_mod_from_profile`前用它提示用户
+    /// # 参数
+    /// - `profile_name`: 配置名
+    /// - `mod_unique_id`: 要移除的模组的UniqueId
+    pub fn broken_by_profile_removal(
+        &self,
+        profile_name: &str,
+        mod_unique_id: &str,
+    ) -> Vec<mods_manager::ModInfo> {
+        let profile_mods = self.get_mods_from_profile(profile_name);
+        let scope: Vec<&str> = profile_mods
+            .iter()
+            .map(|m| m.manifest_info.UniqueId.as_str())
+            .collect();
+        let broken = self
+            .dependency_graph()
+            .broken_by_removal(&[mod_unique_id], Some(&scope));
+        profile_mods
+            .iter()
+            .filter(|m| broken.contains(&m.manifest_info.UniqueId))
+            .cloned()
+            .collect()
+    }
+
+    /// 返回一个模组的笔记和评分, 没有时为空
+    pub fn get_mod_note(&self, mod_unique_id: &str) -> mods_manager::ModNote {
+        self.database_manager
+            .get_cached_mod_note(mod_unique_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// 保存一个模组的笔记和评分
+    /// # 参数
+    /// - `mod_unique_id`: 模组的UniqueId
+    /// - `mod_note`: 笔记和评分, 评分需要在1到5之间
+    pub fn set_mod_note(
+        &mut self,
+        mod_unique_id: &str,
+        mod_note: &mods_manager::ModNote,
+    ) -> Result<()> {
+        if mod_note.rating.is_some_and(|r| !(1..=5).contains(&r)) {
+            return Err(ManagerError::Config("评分需要在1到5之间".to_string()));
+        }
+        Ok(self
+            .database_manager
+            .set_mod_note(mod_unique_id, mod_note)?)
+    }
+
+    /// 把配置导出为json文件, 包含模组列表以及用户的笔记和评分
+    /// # 参数
+    /// - `profile_name`: 配置名
+    /// - `path`: 导出的文件路径
+    pub fn export_profile(&self, profile_name: &str, path: &Path) -> Result<()> {
+        let Some(profile) = self
+            .get_all_profiles()
+            .iter()
+            .find(|p| p.name == profile_name)
+        else {
+            return Err(ManagerError::Config(format!("配置{}不存在", profile_name)));
+        };
+        let mods = self
+            .get_mods_from_profile(profile_name)
+            .iter()
+            .map(|m| {
+                let unique_id = &m.manifest_info.UniqueId;
+                let mod_note = self.get_mod_note(unique_id);
+                ExportedMod {
+                    unique_id: unique_id.clone(),
+                    name: m.manifest_info.Name.clone(),
+                    version: m.manifest_info.Version.clone(),
+                    note: mod_note.note,
+                    rating: mod_note.rating,
+                }
+            })
+            .collect();
+        let export = ProfileExport {
+            name: profile.name.clone(),
+            description: profile.description.clone(),
+            mods,
+        };
+        let json = serde_json::to_string_pretty(&export).map_err(std::io::Error::other)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// 导出依赖图, 范围内不存在的依赖标记为缺失
+    /// # 参数
+    /// - `profile_name`: 只导出该配置中的模组; 为None时导出整个模组库
+    pub fn dependency_graph_export(
+        &self,
+        profile_name: Option<&str>,
+    ) -> Result<DependencyGraphExport> {
+        let mods = match profile_name {
+            None => self.get_registered_mods(),
+            Some(name) => {
+                if !self.get_all_profiles().iter().any(|p| p.name == name) {
+                    return Err(ManagerError::Config(format!("配置{}不存在", name)));
+                }
+                self.get_mods_from_profile(name)
+            }
+        };
+        Ok(DependencyGraphExport::build(mods))
+    }
+
+    /// 按SMAPI的规则计算配置中模组的加载顺序, 并找出无法加载的模组和循环依赖
+    /// # 参数
+    /// - `profile_name`: 配置名
+    pub fn load_order(&self, profile_name: &str) -> Result<LoadOrder> {
+        if !self
+            .get_all_profiles()
+            .iter()
+            .any(|p| p.name == profile_name)
+        {
+            return Err(ManagerError::Config(format!("配置{}不存在", profile_name)));
+        }
+        Ok(load_order::resolve_load_order(
+            self.get_mods_from_profile(profile_name),
+        ))
+    }
+
+    /// 把依赖图写入文件
+    /// - 扩展名为`.json`时写入json, 否则写入Graphviz DOT
+    /// # 参数
+    /// - `profile_name`: 只导出该配置中的模组; 为None时导出整个模组库
+    /// - `path`: 导出的文件路径
+    pub fn export_dependency_graph(&self, profile_name: Option<&str>, path: &Path) -> Result<()> {
+        let graph = self.dependency_graph_export(profile_name)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let text = if is_json {
+            serde_json::to_string_pretty(&graph).map_err(std::io::Error::other)?
+        } else {
+            graph.to_dot()
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    fn registered_mod(&self, mod_unique_id: &str) -> Result<&mods_manager::ModInfo> {
+        self.get_registered_mods()
+            .iter()
+            .find(|m| m.manifest_info.UniqueId == mod_unique_id)
+            .ok_or_else(|| ManagerError::Config(format!("模组{}未注册", mod_unique_id)))
+    }
+
+    /// 模组的config.json的原始文本
+    /// - `profile_name`为Some时优先使用配置保存的版本, 没有时使用模组文件夹中共享的版本
+    /// # 返回值
+    /// 都不存在时为None, 模组还没有生成过config.json
+    fn mod_config_text(
+        &self,
+        mod_unique_id: &str,
+        profile_name: Option<&str>,
+    ) -> Result<Option<String>> {
+        if let Some(profile_name) = profile_name
+            && let Some(config) = self
+                .database_manager
+                .get_profile_mod_configs(profile_name)?
+                .remove(mod_unique_id)
+        {
+            return Ok(Some(config));
+        }
+        let config_path = self.registered_mod(mod_unique_id)?.path.join("config.json");
+        match fs::read_to_string(config_path) {
+            Ok(config) => Ok(Some(config)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 读取模组的config.json并推断每个字段的类型, 用于编辑
+    /// - 内容包的字段可选值来自content.json中的ConfigSchema, 无法解析时错误记录在`schema_error`中
+    /// # 参数
+    /// - `mod_unique_id`: 模组的UniqueId
+    /// - `profile_name`: 编辑该配置保存的版本; 为None时编辑模组文件夹中共享的版本
+    /// # 返回值
+    /// 模组还没有生成过config.json时为None
+    pub fn open_mod_config(
+        &self,
+        mod_unique_id: &str,
+        profile_name: Option<&str>,
+    ) -> Result<Option<mods_manager::ModConfig>> {
+        let Some(text) = self.mod_config_text(mod_unique_id, profile_name)? else {
+            return Ok(None);
+        };
+        let value: serde_json::Value = serde_json::from_str(text.trim_start_matches('\u{feff}'))
+            .map_err(|e| {
+                ManagerError::Config(format!("无法解析{}的config.json: {}", mod_unique_id, e))
+            })?;
+        // content.json无法解析时仍然可以编辑, 只是没有可选值
+        let (allowed, schema_error) =
+            match config_schema::read_allowed_values(&self.registered_mod(mod_unique_id)?.path) {
+                Ok(allowed) => (allowed, None),
+                Err(e) => (HashMap::new(), Some(e.to_string())),
+            };
+        let schema = config_schema::infer_schema(&value, &allowed);
+        Ok(Some(mods_manager::ModConfig {
+            value,
+            schema,
+            schema_error,
+        }))
+    }
+
+    /// 检查并保存修改后的config.json, 被覆盖的版本记录到历史中
+    /// # 参数
+    /// - `mod_unique_id`: 模组的UniqueId
+    /// - `profile_name`: 保存到该配置; 为None时写入模组文件夹中共享的版本
+    /// - `value`: 修改后的内容
+    pub fn save_mod_config(
+        &mut self,
+        mod_unique_id: &str,
+        profile_name: Option<&str>,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        let previous = self.mod_config_text(mod_unique_id, profile_name)?;
+        if let Some(current) = self.open_mod_config(mod_unique_id, profile_name)? {
+            let errors = config_schema::validate(&current.schema, value);
+            if !errors.is_empty() {
+                return Err(ManagerError::Config(errors.join("; ")));
+            }
+        }
+        let config = serde_json::to_string_pretty(value).map_err(std::io::Error::other)?;
+        self.write_mod_config(mod_unique_id, profile_name, previous.as_deref(), &config)
+    }
+
+    /// 恢复config.json的一个历史版本, 当前版本也记录到历史中
+    /// # 参数
+    /// - `mod_unique_id`: 模组的UniqueId
+    /// - `profile_name`: 配置名; 为None时是模组文件夹中共享的版本
+    /// - `version_id`: 历史版本的id, 见`mod_config_history`
+    pub fn revert_mod_config(
+        &mut self,
+        mod_unique_id: &str,
+        profile_name: Option<&str>,
+        version_id: i64,
+    ) -> Result<()> {
+        let Some(version) = self
+            .mod_config_history(mod_unique_id, profile_name)?
+            .into_iter()
+            .find(|v| v.id == version_id)
+        else {
+            return Err(ManagerError::Config(format!(
+                "历史版本{}不存在",
+                version_id
+            )));
+        };
+        let previous = self.mod_config_text(mod_unique_id, profile_name)?;
+        self.write_mod_config(
+            mod_unique_id,
+            profile_name,
+            previous.as_deref(),
+            &version.config,
+        )
+    }
+
+    /// 查询config.json的历史版本, 最新的在前
+    /// # 参数
+    /// - `mod_unique_id`: 模组的UniqueId
+    /// - `profile_name`: 配置名; 为None时是模组文件夹中共享的版本
+    pub fn mod_config_history(
+        &self,
+        mod_unique_id: &str,
+        profile_name: Option<&str>,
+    ) -> Result<Vec<mods_manager::ConfigVersion>> {
+        Ok(self
+            .database_manager
+            .get_mod_config_history(profile_name, mod_unique_id)?)
+    }
+
+    fn write_mod_config(
+        &mut self,
+        mod_unique_id: &str,
+        profile_name: Option<&str>,
+        previous: Option<&str>,
+        config: &str,
+    ) -> Result<()> {
+        if previous == Some(config) {
+            return Ok(());
+        }
+        let config_path = self.registered_mod(mod_unique_id)?.path.join("config.json");
+        self.database_manager.with_transaction(|db| {
+            if let Some(previous) = previous {
+                db.add_mod_config_history(profile_name, mod_unique_id, previous)?;
+            }
+            match profile_name {
+                Some(profile_name) => {
+                    db.set_profile_mod_config(profile_name, mod_unique_id, Some(config))?
+                }
+                None => fs::write(&config_path, config)?,
+            }
+            Ok(())
+        })
+    }
+
     /// 创建一个空的profile
     /// # 参数
     /// - `name`: 配置名
     /// - `description`: 配置描述
-    pub fn create_empty_profile(&mut self, name: &str, description: &str) {
-        let result = self.database_manager.create_profile(name, description);
-        match result {
-            Ok(o) => eprintln!("OK"),
-            Err(e) => eprintln!("{}", e.to_string()),
-        }
-
-        self.link_manager.create_links(&Vec::new(), name);
+    pub fn create_empty_profile(&mut self, name: &str, description: &str) -> Result<()> {
+        self.journaled(|db, link_manager, journal| {
+            if db.create_profile(name, description)? == 0 {
+                return Err(ManagerError::Config(format!("配置{}已存在", name)));
+            }
+            link_manager.create_links(&Vec::new(), name, journal)
+        })
     }
 
     /// 删除一个配置
@@ -121,10 +1288,16 @@ impl Manager {
     /// - `name`: 配置名
     /// # 返回值
     /// 剩余的profile数量
-    pub fn remove_profile(&mut self, name: &str) -> Result<u16, rusqlite::Error> {
-        let num_profiles = self.database_manager.remove_profile(name);
-        self.link_manager.remove_profile(name).unwrap();
-        num_profiles
+    pub fn remove_profile(&mut self, name: &str) -> Result<u16> {
+        let num_profiles = self.journaled(|db, link_manager, journal| {
+            let num_profiles = db.remove_profile(name)?;
+            link_manager.remove_profile(name, journal)?;
+            Ok(num_profiles)
+        })?;
+        if self.get_active_installation().default_profile.as_deref() == Some(name) {
+            self.set_default_profile(None)?;
+        }
+        Ok(num_profiles)
     }
 
     /// 返回所有的profile
@@ -135,43 +1308,499 @@ impl Manager {
     /// 返回一个profile中启用的mod
     /// # 参数
     /// - `profile_name`: profile名
-    pub fn get_mods_from_profile(&self, profile_name: &str) -> Vec<mods_manager::ModInfo> {
-        self.database_manager.get_mods_from_profile(profile_name)
+    pub fn get_mods_from_profile(&self, profile_name: &str) -> &[mods_manager::ModInfo] {
+        self.database_manager
+            .get_cached_mods_from_profile(profile_name)
     }
 
     /// 在指定profile中加入一些模组
     /// # 参数
     /// -  `mods`: 模组的数组, Vec<ModInfo>
     /// - `profile_name`: 配置名
-    pub fn insert_mods_to_profile(&self, mods: Vec<mods_manager::ModInfo>, profile_name: &str) {
-        self.database_manager
-            .insert_mod_to_profile(profile_name, &mods);
-        let mod_path_vec = mods.into_iter().map(|mi| mi.path).collect();
-        self.link_manager.create_links(&mod_path_vec, profile_name);
+    pub fn insert_mods_to_profile(
+        &mut self,
+        mods: Vec<mods_manager::ModInfo>,
+        profile_name: &str,
+    ) -> Result<()> {
+        self.journaled(|db, link_manager, journal| {
+            db.insert_mod_to_profile(profile_name, &mods)?;
+            let mod_path_vec = mods.iter().map(|mi| mi.path.clone()).collect();
+            link_manager.create_links(&mod_path_vec, profile_name, journal)
+        })?;
+        self.sync_default_profile(profile_name)
     }
 
     /// 从指定配置中移除某个模组
-    pub fn remove_mod_from_profile(&self, mod_info: mods_manager::ModInfo, profile_name: &str) {
-        self.database_manager
-            .remove_mod_from_profile(profile_name, mod_info.clone());
-        let mod_path = mod_info.path.clone();
-        match self
-            .link_manager
-            .remove_mod_from_profile(profile_name, mod_path)
+    pub fn remove_mod_from_profile(
+        &mut self,
+        mod_info: mods_manager::ModInfo,
+        profile_name: &str,
+    ) -> Result<()> {
+        self.journaled(|db, link_manager, journal| {
+            db.remove_mod_from_profile(profile_name, mod_info.clone())?;
+            link_manager.remove_mod_from_profile(profile_name, mod_info.path, journal)
+        })?;
+        self.sync_default_profile(profile_name)
+    }
+
+    /// 检查配置目录与数据库记录是否一致
+    /// # 参数
+    /// - `profile_name`: 配置名
+    pub fn verify_profile(&self, profile_name: &str) -> Result<ProfileIntegrityReport> {
+        let mod_path_vec: Vec<PathBuf> = self
+            .get_mods_from_profile(profile_name)
+            .iter()
+            .map(|mi| mi.path.clone())
+            .collect();
+        self.link_manager
+            .verify_profile(profile_name, &mod_path_vec)
+    }
+
+    /// 以数据库记录为准修复配置目录
+    /// # 参数
+    /// - `profile_name`: 配置名
+    /// # 返回值
+    /// 修复后重新检查的结果, 其中是无法自动修复的问题
+    pub fn repair_profile(&self, profile_name: &str) -> Result<ProfileIntegrityReport> {
+        let report = self.verify_profile(profile_name)?;
+        self.link_manager.repair_profile(&report)?;
+        self.verify_profile(profile_name)
+    }
+
+    /// 读取存档目录下的所有存档, 按最后保存时间从新到旧排列
+    /// # 返回值
+    /// 存档, 以及无法读取或解析的存档
+    pub fn discover_saves(&self) -> (Vec<SaveInfo>, Vec<String>) {
+        saves::discover_saves(&self.saves_path)
+    }
+
+    /// 读取配置独立的存档目录中的存档, 配置没有独立的存档目录时为空
+    /// # 返回值
+    /// 同`discover_saves`
+    pub fn discover_profile_saves(&self, profile_name: &str) -> (Vec<SaveInfo>, Vec<String>) {
+        self.profile_saves_path(profile_name)
+            .map(|path| saves::discover_saves(&path))
+            .unwrap_or_default()
+    }
+
+    /// 查询所有存档的绑定配置和游玩记录
+    /// # 返回值
+    /// 存档文件夹名 -> 记录, 从未绑定或游玩过的存档不在其中
+    pub fn get_save_records(&self) -> Result<HashMap<String, mods_manager::SaveRecord>> {
+        Ok(self.database_manager.get_save_records()?)
+    }
+
+    /// 把存档绑定到配置
+    /// # 参数
+    /// - `folder_name`: 存档文件夹名
+    /// - `profile_name`: 配置名, 为None时解除绑定
+    pub fn bind_save_to_profile(
+        &mut self,
+        folder_name: &str,
+        profile_name: Option<&str>,
+    ) -> Result<()> {
+        if let Some(name) = profile_name
+            && !self.get_all_profiles().iter().any(|p| p.name == name)
         {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("{}", e)
+            return Err(ManagerError::Config(format!("配置{}不存在", name)));
+        }
+        Ok(self.database_manager.bind_save(folder_name, profile_name)?)
+    }
+
+    /// 检查绑定到配置的存档, 找出上次游玩时使用但配置中没有的模组
+    /// - 模组的UniqueId不区分大小写
+    /// # 参数
+    /// - `profile_name`: 配置名
+    pub fn save_warnings(&self, profile_name: &str) -> Result<Vec<SaveWarning>> {
+        let in_profile: HashSet<String> = self
+            .get_mods_from_profile(profile_name)
+            .iter()
+            .map(|m| m.manifest_info.UniqueId.to_lowercase())
+            .collect();
+        // 配置独立的存档目录中的存档都视为绑定到该配置
+        let isolated: HashSet<String> = self
+            .discover_profile_saves(profile_name)
+            .0
+            .into_iter()
+            .map(|save| save.folder_name)
+            .collect();
+        let mut warnings: Vec<SaveWarning> = self
+            .database_manager
+            .get_save_records()?
+            .into_iter()
+            .filter(|(folder_name, record)| {
+                record.profile_name.as_deref() == Some(profile_name)
+                    || isolated.contains(folder_name)
+            })
+            .filter_map(|(folder_name, record)| {
+                let missing_mods: Vec<String> = record
+                    .last_mods
+                    .into_iter()
+                    .filter(|id| !in_profile.contains(&id.to_lowercase()))
+                    .collect();
+                (!missing_mods.is_empty()).then_some(SaveWarning {
+                    folder_name,
+                    missing_mods,
+                })
+            })
+            .collect();
+        warnings.sort_by(|a, b| a.folder_name.cmp(&b.folder_name));
+        Ok(warnings)
+    }
+
+    /// 配置独立的存档目录, 使用游戏共享的存档时为None
+    /// # 参数
+    /// - `profile_name`: 配置名
+    pub fn profile_saves_path(&self, profile_name: &str) -> Option<PathBuf> {
+        self.get_all_profiles()
+            .iter()
+            .find(|p| p.name == profile_name)
+            .and_then(|p| p.saves_path.clone())
+    }
+
+    /// 配置独立存档目录的默认位置: 数据目录下的`profile_saves/配置名`
+    pub fn default_profile_saves_path(&self, profile_name: &str) -> PathBuf {
+        self.data_dir.join("profile_saves").join(profile_name)
+    }
+
+    /// 设置配置独立的存档目录, 启动该配置时游戏只能看到其中的存档
+    /// - 目录不存在时创建; 改回共享存档时不会删除原来的目录
+    /// - 启动时通过改名替换存档目录, 需要与游戏的存档目录在同一个磁盘上
+    /// - 不要在游戏运行时调用
+    /// # 参数
+    /// - `profile_name`: 配置名
+    /// - `saves_path`: 存档目录, 为None时使用游戏共享的存档
+    pub fn set_profile_saves_path(
+        &mut self,
+        profile_name: &str,
+        saves_path: Option<PathBuf>,
+    ) -> Result<()> {
+        if !self
+            .get_all_profiles()
+            .iter()
+            .any(|p| p.name == profile_name)
+        {
+            return Err(ManagerError::Config(format!("配置{}不存在", profile_name)));
+        }
+        if let Some(path) = &saves_path {
+            if path.starts_with(&self.saves_path) || self.saves_path.starts_with(path) {
+                return Err(ManagerError::Config(format!(
+                    "配置的存档目录{:?}不能与游戏的存档目录{:?}重叠",
+                    path, self.saves_path
+                )));
             }
+            fs::create_dir_all(path)?;
+        }
+        Ok(self
+            .database_manager
+            .set_profile_saves_path(profile_name, saves_path.as_deref())?)
+    }
+
+    /// 一个存档目录的备份存放位置
+    /// - 游戏的存档目录: 数据目录下的`save_backups`
+    /// - 配置独立的存档目录: `save_backups/.profiles/安装名/配置名`, 与共享存档的同名存档互不影响
+    /// # 参数
+    /// - `profile_name`: 为None时是游戏的存档目录, 否则是该配置独立的存档目录
+    fn backup_store(&self, profile_name: Option<&str>) -> Result<BackupStore> {
+        let root = self.data_dir.join("save_backups");
+        let Some(profile_name) = profile_name else {
+            return Ok(BackupStore::new(root, self.saves_path.clone()));
+        };
+        let saves_path = self.profile_saves_path(profile_name).ok_or_else(|| {
+            ManagerError::Config(format!("配置{}没有独立的存档目录", profile_name))
+        })?;
+        let root = root
+            .join(".profiles")
+            .join(&self.active_installation)
+            .join(profile_name);
+        Ok(BackupStore::new(root, saves_path))
+    }
+
+    /// 备份一个存档, 并按保留策略删除多余的备份
+    /// # 参数
+    /// - `profile_name`: 存档所在的目录, 为None时是游戏的存档目录, 否则是该配置独立的存档目录
+    /// - `folder_name`: 存档文件夹名
+    pub fn backup_save(&self, profile_name: Option<&str>, folder_name: &str) -> Result<SaveBackup> {
+        let store = self.backup_store(profile_name)?;
+        let backup = store.backup(folder_name)?;
+        store.prune(folder_name, &self.backup_retention)?;
+        Ok(backup)
+    }
+
+    /// 一个存档的所有备份, 从新到旧排列
+    /// # 参数
+    /// - `profile_name`: 存档所在的目录, 同`backup_save`
+    /// - `folder_name`: 存档文件夹名
+    pub fn list_save_backups(
+        &self,
+        profile_name: Option<&str>,
+        folder_name: &str,
+    ) -> Result<Vec<SaveBackup>> {
+        Ok(self.backup_store(profile_name)?.list(folder_name))
+    }
+
+    /// 用备份替换存档, 恢复到备份来自的存档目录
+    /// - 替换前先备份当前的存档, 恢复操作本身也可以撤销
+    /// - 不要在游戏运行时调用
+    /// # 参数
+    /// - `backup`: 要恢复的备份, 见`list_save_backups`
+    pub fn restore_save_backup(&self, backup: &SaveBackup) -> Result<()> {
+        if backup.saves_path.join(&backup.folder_name).is_dir() {
+            let store = BackupStore::of(backup);
+            store.backup(&backup.folder_name)?;
+            store.prune(&backup.folder_name, &self.backup_retention)?;
         }
+        save_backup::restore(backup)?;
+        Ok(())
     }
 
-    pub fn launch_stardew_valley(&self, profile_name: &str) {
+    /// 使用指定配置启动SMAPI
+    /// - 启动前把配置保存的config.json写入模组文件夹, 游戏退出后需要调用`finish_game_session`
+    /// - 启动前备份绑定到该配置的存档, 备份失败时不启动
+    /// - 配置使用独立的存档目录时, 运行期间用它替换游戏的存档目录
+    /// - 上次的运行没有恢复时先恢复, 上次启动的游戏仍在运行时不启动
+    /// - 绑定的存档缺少模组时只给出警告, 需要启动前确认的调用者可以先调用`save_warnings`
+    /// # 参数
+    /// - `profile_name`: 配置名
+    /// # 返回值
+    /// 正在运行的游戏, 用于轮询是否退出; 以及不满足的最低版本要求和存档缺少的模组
+    pub fn launch_stardew_valley(&mut self, profile_name: &str) -> Result<LaunchReport> {
+        self.recover_game_session()?;
+        let mut overlays = self
+            .database_manager
+            .get_profile_mod_configs(profile_name)?;
+        let mut warnings = Vec::new();
+        let mods = self.get_mods_from_profile(profile_name);
+        for unmet in compatibility::unmet_requirements(mods, &self.installed_versions) {
+            warnings.push(format!("{}: {}", unmet.unique_id, unmet));
+        }
+        warnings.extend(
+            self.save_warnings(profile_name)?
+                .iter()
+                .map(ToString::to_string),
+        );
+        let records = self.database_manager.get_save_records()?;
+        let (shared_saves, save_problems) = self.discover_saves();
+        warnings.extend(save_problems);
+        for save in shared_saves {
+            let bound = records
+                .get(&save.folder_name)
+                .is_some_and(|r| r.profile_name.as_deref() == Some(profile_name));
+            if bound {
+                self.backup_save(None, &save.folder_name)?;
+            }
+        }
+        let profile_saves_path = self.profile_saves_path(profile_name);
+        if let Some(path) = &profile_saves_path {
+            let (isolated_saves, save_problems) = saves::discover_saves(path);
+            warnings.extend(save_problems);
+            for save in isolated_saves {
+                self.backup_save(Some(profile_name), &save.folder_name)?;
+            }
+        }
+        let mut state = SessionState {
+            profile_name: profile_name.to_string(),
+            started_at: SystemTime::now(),
+            config_swaps: Vec::new(),
+            saves_swap: None,
+            process: None,
+        };
+        for m in self.get_mods_from_profile(profile_name) {
+            let unique_id = &m.manifest_info.UniqueId;
+            let swap = ConfigSwap::new(unique_id, &m.path, overlays.remove(unique_id))?;
+            state.config_swaps.push(swap);
+        }
+        if let Some(path) = &profile_saves_path {
+            state.saves_swap = Some(SavesSwap::new(&self.saves_path, path)?);
+        }
+        // 先写入运行记录再替换config.json和存档目录, 替换中途退出时也能恢复
+        state.save(&self.session_path())?;
+        for swap in &state.config_swaps {
+            if let Err(e) = swap.apply() {
+                return Err(self.abort_launch(
+                    &state,
+                    ManagerError::Config(format!("无法写入{:?}: {}", swap.config_path, e)),
+                ));
+            }
+        }
+        if let Some(swap) = &state.saves_swap
+            && let Err(e) = swap.apply()
+        {
+            return Err(self.abort_launch(
+                &state,
+                ManagerError::Config(format!(
+                    "无法把{:?}替换为配置的存档目录{:?}: {}",
+                    swap.saves_path, swap.profile_saves_path, e
+                )),
+            ));
+        }
+
         let child = Command::new(&self.smapi_path)
             .arg("--mods-path")
             .arg(self.link_manager.link_parent_path.join(profile_name))
-            .spawn()
-            .unwrap();
-        eprintln!("{}已启动", child.id());
+            .spawn();
+        let child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                return Err(self.abort_launch(
+                    &state,
+                    ManagerError::Config(format!("无法启动SMAPI {:?}: {}", self.smapi_path, e)),
+                ));
+            }
+        };
+        state.process = Some(GameProcess::of(child.id()));
+        if let Err(e) = state.save(&self.session_path()) {
+            warnings.push(format!("无法记录游戏进程: {}", e));
+        }
+        Ok(LaunchReport {
+            session: GameSession::new(child, state),
+            warnings,
+        })
+    }
+
+    /// 启动失败时撤销已完成的替换, 并删除运行记录
+    /// # 参数
+    /// - `state`: 启动时的替换记录
+    /// - `error`: 导致启动失败的错误
+    /// # 返回值
+    /// 要返回给调用者的错误, 附加了无法恢复的文件
+    fn abort_launch(&self, state: &SessionState, error: ManagerError) -> ManagerError {
+        let failures = state.restore();
+        let _ = fs::remove_file(self.session_path());
+        if failures.is_empty() {
+            return error;
+        }
+        ManagerError::Config(format!("{}; 恢复未完成: {}", error, failures.join("; ")))
+    }
+
+    /// 游戏退出后, 把运行期间修改的config.json保存到配置, 并恢复共享的版本和游戏的存档目录
+    /// - 某个模组失败时继续处理其余模组, 返回第一个错误, 其余错误加入`warnings`
+    /// # 参数
+    /// - `state`: 启动时的替换记录, 见`GameSession::into_state`
+    /// # 返回值
+    /// 保存了config.json的模组数
+    pub fn finish_game_session(&mut self, state: SessionState) -> Result<usize> {
+        let mut saved = 0;
+        let mut failed = Vec::new();
+        let mut errors: Vec<ManagerError> = Vec::new();
+        for swap in state.config_swaps {
+            let config = match swap.finish() {
+                Ok(Some(config)) => config,
+                Ok(None) => continue,
+                Err(e) => {
+                    errors.push(ManagerError::Config(format!(
+                        "无法恢复{:?}: {}",
+                        swap.config_path, e
+                    )));
+                    failed.push(swap);
+                    continue;
+                }
+            };
+            // 文件已经恢复, 保存失败时不再重试
+            let result = self.database_manager.set_profile_mod_config(
+                &state.profile_name,
+                &swap.unique_id,
+                Some(&config),
+            );
+            match result {
+                Ok(()) => saved += 1,
+                Err(e) => errors.push(ManagerError::Config(format!(
+                    "无法保存{}的config.json: {}",
+                    swap.unique_id, e
+                ))),
+            }
+        }
+        let mut saves_swap = None;
+        let played_saves_path = match state.saves_swap {
+            Some(swap) => {
+                let path = swap.profile_saves_path.clone();
+                if let Err(e) = swap.restore() {
+                    errors.push(ManagerError::Config(format!(
+                        "无法恢复存档目录{:?}: {}",
+                        swap.saves_path, e
+                    )));
+                    saves_swap = Some(swap);
+                }
+                path
+            }
+            None => self.saves_path.clone(),
+        };
+        // 存档目录恢复之后才能读取配置的存档, 未恢复时留到重试时记录
+        if saves_swap.is_none()
+            && let Err(e) =
+                self.record_played_saves(&state.profile_name, state.started_at, &played_saves_path)
+        {
+            errors.push(e);
+        }
+        let mut errors = errors.into_iter();
+        if let Some(e) = errors.next() {
+            self.warnings.extend(errors.map(|e| e.to_string()));
+            // 只保留未能恢复的模组和存档目录, 下次打开时重试
+            let remaining = SessionState {
+                profile_name: state.profile_name,
+                started_at: state.started_at,
+                config_swaps: failed,
+                saves_swap,
+                process: None,
+            };
+            remaining.save(&self.session_path())?;
+            return Err(e);
+        }
+        match fs::remove_file(self.session_path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(saved),
+        }
+    }
+
+    /// 运行期间保存过的存档, 记录为使用该配置中的模组游玩
+    /// # 参数
+    /// - `saves_path`: 游戏运行期间使用的存档所在的目录
+    fn record_played_saves(
+        &mut self,
+        profile_name: &str,
+        started_at: SystemTime,
+        saves_path: &Path,
+    ) -> Result<()> {
+        let mod_unique_ids: Vec<String> = self
+            .get_mods_from_profile(profile_name)
+            .iter()
+            .map(|m| m.manifest_info.UniqueId.clone())
+            .collect();
+        let (played_saves, save_problems) = saves::discover_saves(saves_path);
+        self.warnings.extend(save_problems);
+        for save in played_saves {
+            if save.last_modified.is_some_and(|t| t >= started_at) {
+                self.database_manager
+                    .record_save_played(&save.folder_name, &mod_unique_ids)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn session_path(&self) -> PathBuf {
+        self.data_dir.join("game_session.json")
+    }
+}
+
+/// 模组库需要与模组文件夹在同一个文件系统中, 才能直接移动模组文件夹
+/// - 在`from`中建立一个临时文件夹, 尝试移动到`to`中
+fn check_same_filesystem(from: &Path, to: &Path) -> Result<()> {
+    let probe = from.join(".smm_move_probe");
+    let moved = to.join(".smm_move_probe");
+    let _ = fs::remove_dir(&probe);
+    let _ = fs::remove_dir(&moved);
+    fs::create_dir(&probe)?;
+    match fs::rename(&probe, &moved) {
+        Ok(_) => {
+            let _ = fs::remove_dir(&moved);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = fs::remove_dir(&probe);
+            Err(ManagerError::Config(format!(
+                "无法把模组从{:?}移动到{:?}: {}, 模组库需要与模组文件夹在同一个磁盘上",
+                from, to, e
+            )))
+        }
     }
 }
@@ -1,14 +1,58 @@
+use crate::error::{ManagerError, Result};
+use crate::game_session::GameSession;
+use crate::install_detect::{self, GameInstall};
+use crate::link_manager::ProfileIntegrityReport;
+use crate::mods_manager::LibraryHealthReport;
 use crate::mods_manager::ManifestInfo;
 use crate::mods_manager::ModInfo;
+use crate::mods_manager::config_schema::ConfigField;
+use crate::mods_manager::mods_query::{CategoryFilter, ModQuery, ProfileFilter, SortKey};
+use crate::mods_manager::mods_watcher::ModsWatcher;
+use crate::mods_manager::{ConfigVersion, ModConfig, ModDetails, ModNote, SaveRecord};
+use crate::save_backup::{RetentionPolicy, SaveBackup};
+use crate::saves::{SaveInfo, SaveWarning};
 use crate::total_manager::Manager;
 use eframe::egui;
-use std::path::PathBuf;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 提示消息的显示时长
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// 显示在窗口右下角的提示消息, 一段时间后自动消失
+struct Toast {
+    message: String,
+    is_error: bool,
+    created: Instant,
+}
+
+impl Toast {
+    fn info(message: impl Into<String>) -> Self {
+        Toast {
+            message: message.into(),
+            is_error: false,
+            created: Instant::now(),
+        }
+    }
+
+    fn error(err: impl Display) -> Self {
+        Toast {
+            message: err.to_string(),
+            is_error: true,
+            created: Instant::now(),
+        }
+    }
+}
 
 pub struct StardewModsManagerApp {
     manager: Manager,
     selected_profile: Option<String>,
     selected_mods: std::collections::HashSet<String>, // 存储UniqueId
+    // 模组列表的搜索, 筛选, 排序条件和标签输入
+    mods_query: ModQuery,
+    new_tag_input: String,
     // Profile创建输入
     new_profile_name: String,
     new_profile_desc: String,
@@ -16,32 +60,422 @@ pub struct StardewModsManagerApp {
     data_dir: PathBuf,
     mods_folder_input: String,
     smapi_path_input: String,
+    // 首次使用时检测到的游戏安装, 进入设置界面时检测一次
+    detected_installs: Option<Vec<GameInstall>>,
+    // 添加游戏安装的输入
+    new_installation_name: String,
+    new_installation_game_path: String,
     is_beginner: bool,
+    // 后台监视mods目录, 开启后自动刷新模组列表
+    watch_mods_folder: bool,
+    mods_watcher: Option<ModsWatcher>,
     // 确认对话框状态
     show_reset_confirmation: bool,
+    // 模组库检查结果, 为Some时显示检查窗口
+    library_health: Option<LibraryHealthReport>,
+    // 配置检查结果, 为Some时显示检查窗口
+    profile_integrity: Option<ProfileIntegrityReport>,
+    // 模组详情窗口, 为Some时显示
+    mod_detail: Option<ModDetailState>,
+    // 从配置中移除模组会破坏依赖时, 等待用户确认
+    pending_removal: Option<PendingRemoval>,
+    // config.json编辑窗口, 为Some时显示
+    config_editor: Option<ConfigEditorState>,
+    // 正在运行的游戏, 退出后保存配置的config.json
+    game_session: Option<GameSession>,
+    // 存档列表, 点击刷新时读取
+    saves_path_input: String,
+    saves: Vec<SaveInfo>,
+    // 使用独立存档目录的配置, 以及其中的存档
+    isolated_saves: Vec<(String, Vec<SaveInfo>)>,
+    save_records: std::collections::HashMap<String, SaveRecord>,
+    // (所在的配置, 存档文件夹名) -> 备份, 从新到旧; 游戏存档目录中的存档配置为None
+    save_backups: std::collections::HashMap<(Option<String>, String), Vec<SaveBackup>>,
+    backup_retention: RetentionPolicy,
+    // 绑定的存档缺少模组时, 等待用户确认启动
+    pending_launch: Option<PendingLaunch>,
+    // 操作结果和错误提示
+    toasts: Vec<Toast>,
+}
+
+/// 模组详情窗口的状态, 笔记在点击保存后才写入数据库
+struct ModDetailState {
+    unique_id: String,
+    /// 打开窗口时读取一次
+    details: ModDetails,
+    note: ModNote,
+}
+
+/// 等待确认的移除操作
+struct PendingRemoval {
+    profile_name: String,
+    mod_info: ModInfo,
+    /// 移除后因缺少必需依赖而无法加载的模组
+    broken: Vec<ModInfo>,
+}
+
+/// config.json编辑窗口的状态, 点击保存后才写入
+struct ConfigEditorState {
+    unique_id: String,
+    mod_name: String,
+    /// 为None时编辑模组文件夹中共享的版本
+    profile_name: Option<String>,
+    config: ModConfig,
+    /// 只能按json编辑的字段: 字段路径 -> 正在编辑的文本
+    raw_texts: std::collections::HashMap<String, String>,
+    history: Vec<ConfigVersion>,
+}
+
+/// 按推断出的类型显示config.json的一个字段
+/// # 参数
+/// - `name`: 字段名
+/// - `field`: 字段的类型
+/// - `value`: 字段的值, 编辑时直接修改
+/// - `path`: 字段路径, 用作控件id和`raw_texts`的键
+/// - `raw_texts`: 只能按json编辑的字段正在编辑的文本
+fn ui_config_field(
+    ui: &mut egui::Ui,
+    name: &str,
+    field: &ConfigField,
+    value: &mut serde_json::Value,
+    path: &str,
+    raw_texts: &mut std::collections::HashMap<String, String>,
+) {
+    use serde_json::Value;
+    match (field, value) {
+        (ConfigField::Object(fields), Value::Object(map)) => {
+            ui.collapsing(name, |ui| {
+                for (child_name, child_field) in fields {
+                    if let Some(child) = map.get_mut(child_name) {
+                        let child_path = format!("{}.{}", path, child_name);
+                        ui_config_field(ui, child_name, child_field, child, &child_path, raw_texts);
+                    }
+                }
+            });
+        }
+        (ConfigField::Bool, Value::Bool(b)) => {
+            ui.checkbox(b, name);
+        }
+        (ConfigField::Integer, value) => {
+            let mut n = value.as_i64().unwrap_or_default();
+            ui.horizontal(|ui| {
+                ui.label(name);
+                if ui.add(egui::DragValue::new(&mut n)).changed() {
+                    *value = n.into();
+                }
+            });
+        }
+        (ConfigField::Number, value) => {
+            let mut n = value.as_f64().unwrap_or_default();
+            ui.horizontal(|ui| {
+                ui.label(name);
+                if ui.add(egui::DragValue::new(&mut n).speed(0.1)).changed() {
+                    *value = n.into();
+                }
+            });
+        }
+        (ConfigField::Text, Value::String(text)) => {
+            ui.horizontal(|ui| {
+                ui.label(name);
+                ui.text_edit_singleline(text);
+            });
+        }
+        (
+            ConfigField::Choice {
+                values,
+                multiple: false,
+                ..
+            },
+            Value::String(selected),
+        ) => {
+            ui.horizontal(|ui| {
+                ui.label(name);
+                egui::ComboBox::from_id_salt(path)
+                    .selected_text(selected.as_str())
+                    .show_ui(ui, |ui| {
+                        for v in values {
+                            ui.selectable_value(selected, v.clone(), v);
+                        }
+                    });
+            });
+        }
+        (
+            ConfigField::Choice {
+                values,
+                multiple: true,
+                ..
+            },
+            Value::String(selected),
+        ) => {
+            ui.horizontal_wrapped(|ui| {
+                ui.label(name);
+                for v in values {
+                    let items: Vec<&str> = selected.split(',').map(str::trim).collect();
+                    let mut checked = items.iter().any(|item| item.eq_ignore_ascii_case(v));
+                    if ui.checkbox(&mut checked, v).changed() {
+                        let mut items: Vec<&str> = items
+                            .into_iter()
+                            .filter(|item| !item.is_empty() && !item.eq_ignore_ascii_case(v))
+                            .collect();
+                        if checked {
+                            items.push(v);
+                        }
+                        *selected = items.join(", ");
+                    }
+                }
+            });
+        }
+        (_, value) => {
+            let text = raw_texts
+                .entry(path.to_string())
+                .or_insert_with(|| serde_json::to_string_pretty(value).unwrap_or_default());
+            ui.label(name);
+            if ui.code_editor(text).changed()
+                && let Ok(parsed) = serde_json::from_str(text)
+            {
+                *value = parsed;
+            }
+            if serde_json::from_str::<Value>(text).is_err() {
+                ui.colored_label(egui::Color32::from_rgb(200, 40, 40), "json格式错误");
+            }
+        }
+    }
+}
+
+/// 等待确认的启动操作
+struct PendingLaunch {
+    profile_name: String,
+    warnings: Vec<SaveWarning>,
+}
+
+/// 把字节数显示为KB/MB
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// 一个存档的备份列表
+/// # 返回值
+/// 点击了恢复的备份
+fn ui_backup_list(
+    ui: &mut egui::Ui,
+    id_salt: impl std::hash::Hash,
+    backups: &[SaveBackup],
+    enabled: bool,
+) -> Option<SaveBackup> {
+    let mut restore = None;
+    if backups.is_empty() {
+        return restore;
+    }
+    egui::CollapsingHeader::new(format!("备份({})", backups.len()))
+        .id_salt(id_salt)
+        .show(ui, |ui| {
+            for b in backups {
+                ui.horizontal(|ui| {
+                    ui.label(format_elapsed(b.created_at));
+                    if ui.add_enabled(enabled, egui::Button::new("恢复")).clicked() {
+                        restore = Some(b.clone());
+                    }
+                });
+            }
+        });
+    restore
+}
+
+/// 把时间显示为距今多久
+fn format_elapsed(time: std::time::SystemTime) -> String {
+    let secs = time.elapsed().map_or(0, |d| d.as_secs());
+    match secs {
+        0..60 => "刚刚".to_string(),
+        60..3600 => format!("{}分钟前", secs / 60),
+        3600..86400 => format!("{}小时前", secs / 3600),
+        _ => format!("{}天前", secs / 86400),
+    }
 }
 
 impl StardewModsManagerApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let manager = Manager::default();
+    pub fn new(cc: &eframe::CreationContext<'_>) -> std::result::Result<Self, ManagerError> {
+        let mut manager = Manager::default()?;
 
         let data_dir = dirs::data_dir()
             .unwrap_or_else(|| std::env::current_dir().unwrap())
             .join("StardewModsManager");
         let config_path = data_dir.join("setting.toml");
         let is_beginner = !config_path.exists();
+        let watch_mods_folder = crate::config::AppConfig::load_from_file(&config_path)
+            .is_ok_and(|cfg| cfg.watch_mods_folder);
+        let saves_path_input = manager.get_saves_path().display().to_string();
+        let backup_retention = manager.get_backup_retention();
+        let mut toasts: Vec<Toast> = manager
+            .take_warnings()
+            .into_iter()
+            .map(Toast::error)
+            .collect();
+        let mods_watcher = if watch_mods_folder {
+            manager
+                .watch_mods_folder()
+                .inspect_err(|e| toasts.push(Toast::error(e)))
+                .ok()
+        } else {
+            None
+        };
 
-        Self {
+        let mut app = Self {
             manager,
             selected_profile: None,
             selected_mods: Default::default(),
+            mods_query: ModQuery::default(),
+            new_tag_input: String::new(),
             data_dir,
             new_profile_name: String::new(),
             new_profile_desc: String::new(),
             mods_folder_input: String::new(),
             smapi_path_input: String::new(),
+            detected_installs: None,
+            new_installation_name: String::new(),
+            new_installation_game_path: String::new(),
             is_beginner,
+            watch_mods_folder,
+            mods_watcher,
             show_reset_confirmation: false,
+            library_health: None,
+            profile_integrity: None,
+            mod_detail: None,
+            pending_removal: None,
+            config_editor: None,
+            game_session: None,
+            saves_path_input,
+            saves: Vec::new(),
+            isolated_saves: Vec::new(),
+            save_records: Default::default(),
+            save_backups: Default::default(),
+            backup_retention,
+            pending_launch: None,
+            toasts,
+        };
+        app.refresh_saves();
+        Ok(app)
+    }
+
+    /// 成功时返回结果, 失败时显示错误提示
+    /// - 同时取出操作中积累的警告
+    fn report<T>(&mut self, result: Result<T>) -> Option<T> {
+        let value = result
+            .inspect_err(|e| self.toasts.push(Toast::error(e)))
+            .ok();
+        let warnings = self.manager.take_warnings();
+        self.toasts.extend(warnings.into_iter().map(Toast::error));
+        value
+    }
+
+    /// 扫描并注册所有模组, manifest无法解析的模组逐个提示
+    fn register_all_mods(&mut self) {
+        let result = self.manager.register_all_mods();
+        if let Some(errors) = self.report(result) {
+            self.toasts.extend(errors.into_iter().map(Toast::error));
+        }
+    }
+
+    /// 右下角的提示消息
+    fn ui_toasts(&mut self, ctx: &egui::Context) {
+        self.toasts
+            .retain(|toast| toast.created.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-16.0, -16.0])
+            .show(ctx, |ui| {
+                for toast in &self.toasts {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        if toast.is_error {
+                            ui.colored_label(egui::Color32::from_rgb(200, 40, 40), &toast.message);
+                        } else {
+                            ui.label(&toast.message);
+                        }
+                    });
+                }
+            });
+        // 到时间后需要重绘才能消失
+        ctx.request_repaint_after(Duration::from_millis(500));
+    }
+
+    /// 开启或关闭mods目录的监视, 并写入配置文件
+    fn set_watch_mods_folder(&mut self, enabled: bool) {
+        self.mods_watcher = None;
+        self.watch_mods_folder = false;
+        if enabled {
+            match self.manager.watch_mods_folder() {
+                Ok(watcher) => {
+                    // 监视只推送之后的变化, 开启时先完整扫描一次
+                    self.register_all_mods();
+                    self.mods_watcher = Some(watcher);
+                    self.watch_mods_folder = true;
+                }
+                Err(e) => self.toasts.push(Toast::error(e)),
+            }
+        }
+
+        let config_path = self.data_dir.join("setting.toml");
+        let result = crate::config::AppConfig::load_from_file(&config_path).and_then(|mut cfg| {
+            cfg.watch_mods_folder = self.watch_mods_folder;
+            cfg.save_to_file(&config_path)
+        });
+        self.report(result);
+    }
+
+    /// 取出监视器推送的模组变化并同步
+    fn poll_mods_watcher(&mut self, ctx: &egui::Context) {
+        let Some(watcher) = &self.mods_watcher else {
+            return;
+        };
+        let changes: Vec<_> = std::iter::from_fn(|| watcher.try_recv()).collect();
+        for change in changes {
+            for removed in &change.removed {
+                self.selected_mods.remove(&removed.manifest_info.UniqueId);
+            }
+            self.toasts.extend(change.errors.iter().map(Toast::error));
+            let result = self.manager.apply_mods_change(&change);
+            self.report(result);
+            if !change.removed.is_empty() {
+                self.toasts.push(Toast::info(format!(
+                    "{}个模组的文件夹已不存在, 可以在模组库检查中清理",
+                    change.removed.len()
+                )));
+            }
+        }
+        // 没有用户输入时ui不会重绘, 这里定时唤醒以取出变化
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+
+    /// 检查游戏是否已经退出, 退出后保存配置的config.json
+    fn poll_game_session(&mut self, ctx: &egui::Context) {
+        let Some(session) = &mut self.game_session else {
+            return;
+        };
+        match session.has_exited() {
+            Ok(false) => {
+                ctx.request_repaint_after(Duration::from_secs(1));
+                return;
+            }
+            Ok(true) => {}
+            Err(e) => self.toasts.push(Toast::error(e)),
+        }
+        let Some(session) = self.game_session.take() else {
+            return;
+        };
+        let profile_name = session.profile_name().to_string();
+        let result = self.manager.finish_game_session(session.into_state());
+        if let Some(saved) = self.report(result)
+            && saved > 0
+        {
+            self.toasts.push(Toast::info(format!(
+                "已保存{}个模组的config.json到配置{}",
+                saved, profile_name
+            )));
         }
     }
 
@@ -92,10 +526,36 @@ impl StardewModsManagerApp {
     }
 
     fn ui_beginner_setting(&mut self, ui: &mut egui::Ui) {
+        if self.detected_installs.is_none() {
+            self.detect_installs();
+        }
         ui.heading("首次使用?请填写模组文件夹路径和smapi路径, 格式默认如下:");
         ui.label("C:\\Program Files (x86)\\Steam\\steamapps\\common\\Stardew Valley\\Mods");
         ui.label("C:\\Program Files (x86)\\Steam\\steamapps\\common\\Stardew Valley\\StardewModdingAPI.exe");
 
+        let installs = self.detected_installs.clone().unwrap_or_default();
+        ui.horizontal(|ui| {
+            ui.label(format!("检测到{}处游戏安装", installs.len()));
+            if ui.button("重新检测").clicked() {
+                self.detect_installs();
+            }
+        });
+        for install in &installs {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "[{}] {}",
+                    install.source,
+                    install.game_path.display()
+                ));
+                if install.smapi_path.is_none() {
+                    ui.weak("未安装SMAPI");
+                }
+                if ui.button("使用").clicked() {
+                    self.use_install(install);
+                }
+            });
+        }
+
         ui.horizontal(|ui| {
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
@@ -119,24 +579,50 @@ impl StardewModsManagerApp {
                 });
 
                 if ui.button("保存").highlight().clicked() {
-                    if (!self.mods_folder_input.trim().is_empty())
-                        && (!self.smapi_path_input.trim().is_empty())
-                        && (self.smapi_path_input.trim().ends_with("exe"))
-                    {
+                    let installed_versions = self
+                        .manager
+                        .detect_installed_versions(Path::new(self.smapi_path_input.trim()));
+                    let cfg = crate::config::AppConfig {
+                        mods_folder_path: self.mods_folder_input.trim().to_string(),
+                        smapi_path: self.smapi_path_input.trim().to_string(),
+                        watch_mods_folder: self.watch_mods_folder,
+                        saves_path: None,
+                        backup_retention: Default::default(),
+                        installed_versions,
+                        installations: Vec::new(),
+                        active_installation: None,
+                        profiles_root: Some(
+                            self.manager
+                                .get_profiles_root()
+                                .to_string_lossy()
+                                .to_string(),
+                        ),
+                    };
+                    if self.report(cfg.validate()).is_some() {
                         let config_path = self.data_dir.join("setting.toml");
-                        let cfg = crate::config::AppConfig {
-                            mods_folder_path: self.mods_folder_input.clone(),
-                            smapi_path: self.smapi_path_input.clone(),
-                        };
                         if let Err(e) = cfg.save_to_file(&config_path) {
-                            ui.label(format!("保存失败: {}", e));
+                            self.toasts.push(Toast::error(format!("保存失败: {}", e)));
                         } else {
                             // 设置scanner路径并隐藏设置界面
                             self.manager.set_scanner_mods_path(std::path::PathBuf::from(
-                                &self.mods_folder_input,
+                                &cfg.mods_folder_path,
                             ));
+                            self.manager.set_smapi_path(PathBuf::from(&cfg.smapi_path));
+                            // 配置目录放在所选游戏的文件夹中
+                            if let Some(game_path) = Path::new(&cfg.smapi_path).parent() {
+                                let installation = crate::config::Installation::from_game_path(
+                                    crate::config::DEFAULT_INSTALLATION,
+                                    game_path,
+                                );
+                                let result = self
+                                    .manager
+                                    .set_profiles_root(Path::new(&installation.profiles_root));
+                                self.report(result);
+                            }
+                            self.manager
+                                .set_installed_versions(cfg.installed_versions.clone());
                             self.is_beginner = false;
-                            self.manager.register_all_mods();
+                            self.register_all_mods();
                         }
                     }
                 }
@@ -144,6 +630,135 @@ impl StardewModsManagerApp {
         });
     }
 
+    /// 检测游戏安装, 输入框为空时填入第一个结果
+    fn detect_installs(&mut self) {
+        let installs = install_detect::detect_installs();
+        if self.mods_folder_input.trim().is_empty()
+            && self.smapi_path_input.trim().is_empty()
+            && let Some(first) = installs.first()
+        {
+            self.use_install(first);
+        }
+        self.detected_installs = Some(installs);
+    }
+
+    /// 用一处游戏安装填写模组文件夹和SMAPI路径
+    /// - 未安装SMAPI时填入SMAPI安装后的默认位置
+    fn use_install(&mut self, install: &GameInstall) {
+        self.mods_folder_input = install.mods_path().display().to_string();
+        self.smapi_path_input = install
+            .smapi_path
+            .clone()
+            .unwrap_or_else(|| install.game_path.join(install_detect::SMAPI_EXECUTABLE))
+            .display()
+            .to_string();
+    }
+
+    /// 切换, 添加和移除游戏安装
+    /// - 游戏运行时不能切换
+    fn ui_installations(&mut self, ui: &mut egui::Ui) {
+        let active = self.manager.get_active_installation().name.clone();
+        let names: Vec<String> = self
+            .manager
+            .get_installations()
+            .iter()
+            .map(|i| i.name.clone())
+            .collect();
+        let mut selected = active.clone();
+        ui.horizontal(|ui| {
+            ui.label("游戏安装:");
+            ui.add_enabled_ui(self.game_session.is_none(), |ui| {
+                egui::ComboBox::from_id_salt("installation")
+                    .selected_text(&selected)
+                    .show_ui(ui, |ui| {
+                        for name in &names {
+                            ui.selectable_value(&mut selected, name.clone(), name);
+                        }
+                    });
+            });
+        });
+        if selected != active {
+            let result = self.manager.switch_installation(&selected);
+            if self.report(result).is_some() {
+                let warnings = self.manager.take_warnings();
+                self.toasts.extend(warnings.into_iter().map(Toast::error));
+                self.selected_profile = None;
+                self.selected_mods.clear();
+                if self.watch_mods_folder {
+                    self.set_watch_mods_folder(true);
+                }
+                self.toasts
+                    .push(Toast::info(format!("已切换到{}", selected)));
+            }
+        }
+
+        let mut remove = None;
+        let mut add = false;
+        ui.collapsing("管理游戏安装", |ui| {
+            for installation in self.manager.get_installations() {
+                ui.horizontal(|ui| {
+                    ui.label(&installation.name);
+                    ui.weak(&installation.game_path);
+                    if installation.name != active && ui.button("移除").clicked() {
+                        remove = Some(installation.name.clone());
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.new_installation_name)
+                        .hint_text("安装名, 如测试版")
+                        .desired_width(120.0),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.new_installation_game_path)
+                        .hint_text("游戏文件夹")
+                        .desired_width(400.0),
+                );
+                add = ui.button("添加").clicked();
+            });
+            // 检测到但还没有添加的安装
+            for install in self
+                .detected_installs
+                .get_or_insert_with(install_detect::detect_installs)
+            {
+                let added = self
+                    .manager
+                    .get_installations()
+                    .iter()
+                    .any(|i| Path::new(&i.game_path) == install.game_path);
+                if added {
+                    continue;
+                }
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "[{}] {}",
+                        install.source,
+                        install.game_path.display()
+                    ));
+                    if ui.button("填入").clicked() {
+                        self.new_installation_game_path = install.game_path.display().to_string();
+                    }
+                });
+            }
+        });
+        if add {
+            let installation = crate::config::Installation::from_game_path(
+                &self.new_installation_name,
+                Path::new(self.new_installation_game_path.trim()),
+            );
+            let result = self.manager.add_installation(installation);
+            if self.report(result).is_some() {
+                self.new_installation_name.clear();
+                self.new_installation_game_path.clear();
+            }
+        }
+        if let Some(name) = remove {
+            let result = self.manager.remove_installation(&name);
+            self.report(result);
+        }
+    }
+
     /// - mods列表 组件
     /// - 提供选中功能
     /// Todo: 删除操作后续改为先收集删除名单, 再统一删除
@@ -151,15 +766,44 @@ impl StardewModsManagerApp {
         ui.heading("所有模组");
         // 只有填写并保存路径后才显示扫描按钮
         if !self.is_beginner {
-            if ui.button("扫描模组").highlight().clicked() {
-                self.manager.register_all_mods();
-                self.selected_mods.clear();
-            }
+            ui.horizontal(|ui| {
+                if ui.button("扫描模组").highlight().clicked() {
+                    self.register_all_mods();
+                    self.selected_mods.clear();
+                }
+                if ui.button("模组库检查").highlight().clicked() {
+                    let result = self.manager.check_library_health();
+                    self.library_health = self.report(result);
+                }
+                let mut watch = self.watch_mods_folder;
+                if ui.checkbox(&mut watch, "自动刷新").changed() {
+                    self.set_watch_mods_folder(watch);
+                }
+                if ui.button("导出依赖图").clicked() {
+                    self.export_dependency_graph(None);
+                }
+            });
         }
+        if !self.is_beginner {
+            let versions = self.manager.get_installed_versions();
+            ui.weak(format!(
+                "SMAPI {} / 游戏 {}",
+                versions.smapi.as_deref().unwrap_or("版本未知"),
+                versions.game.as_deref().unwrap_or("版本未知")
+            ));
+            self.ui_mods_library(ui);
+            self.ui_profiles_root(ui);
+        }
+        self.ui_mods_filter(ui);
+        let unmet = self.manager.unmet_version_requirements();
+        // 点击标签上的×后移除, 循环中manager被借用, 结束后再执行
+        let mut untag: Option<(String, String)> = None;
+        let mut open_detail: Option<String> = None;
         egui::ScrollArea::vertical()
             .max_height(240.0)
             .show(ui, |ui| {
-                for modinfo in self.manager.get_registered_mods() {
+                let mods = self.manager.query_mods(&self.mods_query);
+                for modinfo in mods {
                     let unique_id = &modinfo.manifest_info.UniqueId;
                     let mut checked = self.selected_mods.contains(unique_id);
                     ui.horizontal(|ui| {
@@ -170,17 +814,57 @@ impl StardewModsManagerApp {
                                 self.selected_mods.remove(unique_id);
                             }
                         }
-                        ui.label(&modinfo.manifest_info.Name);
+                        if ui
+                            .link(&modinfo.manifest_info.Name)
+                            .on_hover_text("查看详情")
+                            .clicked()
+                        {
+                            open_detail = Some(unique_id.clone());
+                        }
                         ui.label(&modinfo.manifest_info.Version);
+                        ui.weak(modinfo.manifest_info.category().to_string());
+                        for requirement in unmet.iter().filter(|u| &u.unique_id == unique_id) {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(200, 40, 40),
+                                format!("⚠ {}", requirement),
+                            );
+                        }
+                        for tag in self.manager.get_tags_of_mod(unique_id) {
+                            if ui
+                                .small_button(format!("{} ×", tag))
+                                .on_hover_text("移除标签")
+                                .clicked()
+                            {
+                                untag = Some((unique_id.clone(), tag.clone()));
+                            }
+                        }
                         ui.label(&modinfo.manifest_info.Description);
                     });
                 }
             });
-        if let Some(profile_name) = &self.selected_profile {
-            let button_content = format!(
-                "选中的模组添加到{}",
-                self.selected_profile.as_ref().unwrap()
+        if let Some((unique_id, tag)) = untag {
+            let result = self.manager.untag_mod(&unique_id, &tag);
+            self.report(result);
+        }
+        if let Some(unique_id) = open_detail {
+            self.open_mod_detail(unique_id);
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_tag_input)
+                    .hint_text("标签名")
+                    .desired_width(120.0),
             );
+            if ui.button("给选中的模组添加标签").clicked() {
+                let ids: Vec<String> = self.selected_mods.iter().cloned().collect();
+                let result = self.manager.tag_mods(&ids, &self.new_tag_input);
+                if self.report(result).is_some() {
+                    self.new_tag_input.clear();
+                }
+            }
+        });
+        if let Some(profile_name) = self.selected_profile.clone() {
+            let button_content = format!("选中的模组添加到{}", profile_name);
             if ui.button(button_content).highlight().clicked() {
                 let all_mods = self.manager.get_registered_mods();
                 let to_add: Vec<_> = all_mods
@@ -188,19 +872,846 @@ impl StardewModsManagerApp {
                     .filter(|m| self.selected_mods.contains(&m.manifest_info.UniqueId))
                     .cloned()
                     .collect();
-                self.manager.insert_mods_to_profile(to_add, profile_name);
+                let result = self.manager.insert_mods_to_profile(to_add, &profile_name);
+                self.report(result);
                 // 然后清空选中的模组
                 self.selected_mods.clear();
             }
         }
     }
 
+    /// 模组列表的搜索栏, 筛选和排序
+    fn ui_mods_filter(&mut self, ui: &mut egui::Ui) {
+        // "在当前配置中"跟随选中的配置
+        if let ProfileFilter::InProfile(name) = &self.mods_query.profile {
+            match &self.selected_profile {
+                Some(selected) if selected != name => {
+                    self.mods_query.profile = ProfileFilter::InProfile(selected.clone())
+                }
+                None => self.mods_query.profile = ProfileFilter::All,
+                _ => {}
+            }
+        }
+
+        let query = &mut self.mods_query;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut query.search)
+                    .hint_text("搜索名称, UniqueId, 描述, 作者")
+                    .desired_width(220.0),
+            );
+            egui::ComboBox::from_label("排序")
+                .selected_text(query.sort.label())
+                .show_ui(ui, |ui| {
+                    for key in SortKey::ALL {
+                        ui.selectable_value(&mut query.sort, key, key.label());
+                    }
+                });
+            ui.checkbox(&mut query.descending, "降序");
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("标签")
+                .selected_text(query.tag.as_deref().unwrap_or("全部"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut query.tag, None, "全部");
+                    for tag in self.manager.get_all_tags() {
+                        let text = tag.clone();
+                        ui.selectable_value(&mut query.tag, Some(tag), text);
+                    }
+                });
+            let category_text = match &query.category {
+                None => "全部".to_string(),
+                Some(CategoryFilter::AnyContentPack) => "所有内容包".to_string(),
+                Some(CategoryFilter::Exact(category)) => category.to_string(),
+            };
+            egui::ComboBox::from_label("分类")
+                .selected_text(category_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut query.category, None, "全部");
+                    ui.selectable_value(
+                        &mut query.category,
+                        Some(CategoryFilter::AnyContentPack),
+                        "所有内容包",
+                    );
+                    for category in self.manager.get_all_categories() {
+                        let text = category.to_string();
+                        ui.selectable_value(
+                            &mut query.category,
+                            Some(CategoryFilter::Exact(category)),
+                            text,
+                        );
+                    }
+                });
+            let profile_text = match &query.profile {
+                ProfileFilter::All => "全部".to_string(),
+                ProfileFilter::InProfile(name) => format!("在{}中", name),
+                ProfileFilter::NotInAnyProfile => "不在任何配置中".to_string(),
+            };
+            egui::ComboBox::from_label("配置")
+                .selected_text(profile_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut query.profile, ProfileFilter::All, "全部");
+                    if let Some(selected) = &self.selected_profile {
+                        ui.selectable_value(
+                            &mut query.profile,
+                            ProfileFilter::InProfile(selected.clone()),
+                            format!("在{}中", selected),
+                        );
+                    }
+                    ui.selectable_value(
+                        &mut query.profile,
+                        ProfileFilter::NotInAnyProfile,
+                        "不在任何配置中",
+                    );
+                });
+        });
+    }
+
+    /// 读取模组详情并打开详情窗口
+    fn open_mod_detail(&mut self, unique_id: String) {
+        let result = self.manager.mod_details(&unique_id);
+        if let Some(details) = self.report(result) {
+            let note = self.manager.get_mod_note(&unique_id);
+            self.mod_detail = Some(ModDetailState {
+                unique_id,
+                details,
+                note,
+            });
+        }
+    }
+
+    /// 打开config.json编辑窗口
+    /// # 参数
+    /// - `profile_name`: 编辑该配置保存的版本; 为None时编辑模组文件夹中共享的版本
+    fn open_config_editor(&mut self, unique_id: &str, profile_name: Option<&str>) {
+        let result = self.manager.open_mod_config(unique_id, profile_name);
+        let Some(config) = self.report(result) else {
+            return;
+        };
+        let Some(config) = config else {
+            self.toasts.push(Toast::info(
+                "该模组还没有config.json, 运行一次游戏后会自动生成",
+            ));
+            return;
+        };
+        let result = self.manager.mod_config_history(unique_id, profile_name);
+        let history = self.report(result).unwrap_or_default();
+        let mod_name = self
+            .manager
+            .get_registered_mods()
+            .iter()
+            .find(|m| m.manifest_info.UniqueId == unique_id)
+            .map_or_else(|| unique_id.to_string(), |m| m.manifest_info.Name.clone());
+        self.config_editor = Some(ConfigEditorState {
+            unique_id: unique_id.to_string(),
+            mod_name,
+            profile_name: profile_name.map(str::to_string),
+            config,
+            raw_texts: Default::default(),
+            history,
+        });
+    }
+
+    /// config.json编辑窗口
+    /// - 按推断出的类型显示表单, 保存前检查
+    /// - 列出历史版本, 可以恢复
+    fn ui_config_editor(&mut self, ctx: &egui::Context) {
+        let Some(state) = &mut self.config_editor else {
+            return;
+        };
+        let mut close = false;
+        let mut save = false;
+        let mut revert = None;
+        let title = match &state.profile_name {
+            Some(profile_name) => format!("{}的config.json (配置{})", state.mod_name, profile_name),
+            None => format!("{}的config.json", state.mod_name),
+        };
+        egui::Window::new(title)
+            .id(egui::Id::new("config_editor"))
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if let Some(e) = &state.config.schema_error {
+                    ui.colored_label(egui::Color32::RED, e);
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        match (&state.config.schema, &mut state.config.value) {
+                            // 顶层字段直接显示, 不折叠
+                            (ConfigField::Object(fields), serde_json::Value::Object(map)) => {
+                                for (name, field) in fields {
+                                    if let Some(value) = map.get_mut(name) {
+                                        ui_config_field(
+                                            ui,
+                                            name,
+                                            field,
+                                            value,
+                                            name,
+                                            &mut state.raw_texts,
+                                        );
+                                    }
+                                }
+                            }
+                            (schema, value) => {
+                                ui_config_field(
+                                    ui,
+                                    "config",
+                                    schema,
+                                    value,
+                                    "config",
+                                    &mut state.raw_texts,
+                                );
+                            }
+                        }
+                    });
+                ui.collapsing(format!("历史版本({})", state.history.len()), |ui| {
+                    for version in &state.history {
+                        ui.horizontal(|ui| {
+                            ui.label(&version.saved_at);
+                            if ui.button("恢复").clicked() {
+                                revert = Some(version.id);
+                            }
+                        });
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("保存").clicked() {
+                        save = true;
+                    }
+                    if ui.button("关闭").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        let unique_id = state.unique_id.clone();
+        let profile_name = state.profile_name.clone();
+        if save {
+            let invalid: Vec<&str> = state
+                .raw_texts
+                .iter()
+                .filter(|(_, text)| serde_json::from_str::<serde_json::Value>(text).is_err())
+                .map(|(path, _)| path.as_str())
+                .collect();
+            let result = if invalid.is_empty() {
+                let value = state.config.value.clone();
+                self.manager
+                    .save_mod_config(&unique_id, profile_name.as_deref(), &value)
+            } else {
+                Err(ManagerError::Config(format!(
+                    "json格式错误: {}",
+                    invalid.join(", ")
+                )))
+            };
+            if self.report(result).is_some() {
+                self.toasts.push(Toast::info("config.json已保存"));
+                self.open_config_editor(&unique_id, profile_name.as_deref());
+            }
+        }
+        if let Some(version_id) = revert {
+            let result =
+                self.manager
+                    .revert_mod_config(&unique_id, profile_name.as_deref(), version_id);
+            if self.report(result).is_some() {
+                self.toasts.push(Toast::info("已恢复历史版本"));
+                self.open_config_editor(&unique_id, profile_name.as_deref());
+            }
+        }
+        if close {
+            self.config_editor = None;
+        }
+    }
+
+    /// 模组详情窗口
+    /// - 显示完整manifest, 文件夹统计, 依赖, 被依赖和所在配置
+    /// - 编辑笔记和评分
+    fn ui_mod_detail(&mut self, ctx: &egui::Context) {
+        let Some(state) = &mut self.mod_detail else {
+            return;
+        };
+        let mut close = false;
+        let mut save = false;
+        let mut edit_config = false;
+        let mut open_detail = None;
+        egui::Window::new("模组详情")
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                let details = &state.details;
+                let mod_info = &details.mod_info;
+                let manifest = &mod_info.manifest_info;
+                ui.heading(&manifest.Name);
+                egui::Grid::new("mod_detail_grid").show(ui, |ui| {
+                    ui.label("版本");
+                    ui.label(&manifest.Version);
+                    ui.end_row();
+                    ui.label("UniqueId");
+                    ui.label(&manifest.UniqueId);
+                    ui.end_row();
+                    ui.label("作者");
+                    ui.label(&manifest.Author);
+                    ui.end_row();
+                    ui.label("分类");
+                    ui.label(manifest.category().to_string());
+                    ui.end_row();
+                    ui.label("路径");
+                    ui.label(mod_info.path.display().to_string());
+                    ui.end_row();
+                    ui.label("大小");
+                    ui.label(format!(
+                        "{}, {}个文件",
+                        format_size(details.disk_size),
+                        details.file_count
+                    ));
+                    ui.end_row();
+                    ui.label("最后修改");
+                    ui.label(
+                        details
+                            .last_modified
+                            .map_or("未知".to_string(), format_elapsed),
+                    );
+                    ui.end_row();
+                    ui.label("所在配置");
+                    ui.label(if details.profiles.is_empty() {
+                        "无".to_string()
+                    } else {
+                        details.profiles.join(", ")
+                    });
+                    ui.end_row();
+                });
+                ui.label(&manifest.Description);
+
+                ui.collapsing(format!("依赖({})", details.dependencies.len()), |ui| {
+                    for status in &details.dependencies {
+                        let dependency = &status.dependency;
+                        let required = if dependency.IsRequired {
+                            ""
+                        } else {
+                            " (可选)"
+                        };
+                        match &status.installed_version {
+                            Some(version) => ui.label(format!(
+                                "✔ {} {}{}",
+                                dependency.UniqueId, version, required
+                            )),
+                            None => ui.colored_label(
+                                egui::Color32::from_rgb(200, 40, 40),
+                                format!("✘ {} 未安装{}", dependency.UniqueId, required),
+                            ),
+                        };
+                    }
+                });
+                ui.collapsing(format!("被依赖({})", details.dependents.len()), |ui| {
+                    for dependent in &details.dependents {
+                        ui.horizontal(|ui| {
+                            let manifest = &dependent.mod_info.manifest_info;
+                            if ui.link(&manifest.Name).clicked() {
+                                open_detail = Some(manifest.UniqueId.clone());
+                            }
+                            if dependent.required {
+                                ui.label("移除后无法加载");
+                            } else {
+                                ui.weak("可选依赖");
+                            }
+                            if !dependent.profiles.is_empty() {
+                                ui.weak(format!("配置: {}", dependent.profiles.join(", ")));
+                            }
+                        });
+                    }
+                });
+                ui.collapsing("完整manifest", |ui| match &details.manifest {
+                    Ok(value) => {
+                        let text = serde_json::to_string_pretty(value).unwrap_or_default();
+                        ui.monospace(text);
+                    }
+                    Err(e) => {
+                        ui.colored_label(egui::Color32::from_rgb(200, 40, 40), e);
+                    }
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("评分");
+                    for star in 1..=5u8 {
+                        let lit = state.note.rating.is_some_and(|r| r >= star);
+                        if ui
+                            .selectable_label(lit, if lit { "★" } else { "☆" })
+                            .clicked()
+                        {
+                            // 再次点击当前评分则取消
+                            state.note.rating = if state.note.rating == Some(star) {
+                                None
+                            } else {
+                                Some(star)
+                            };
+                        }
+                    }
+                });
+                ui.label("笔记");
+                ui.add(
+                    egui::TextEdit::multiline(&mut state.note.note)
+                        .hint_text("如: 与SVE 1.14冲突")
+                        .desired_rows(4),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("保存").clicked() {
+                        save = true;
+                    }
+                    if ui.button("关闭").clicked() {
+                        close = true;
+                    }
+                    if ui.button("编辑config.json").clicked() {
+                        edit_config = true;
+                    }
+                });
+            });
+        if edit_config {
+            let unique_id = state.unique_id.clone();
+            self.open_config_editor(&unique_id, None);
+        }
+        if save {
+            let state = self.mod_detail.as_ref().unwrap();
+            let result = self.manager.set_mod_note(&state.unique_id, &state.note);
+            if self.report(result).is_some() {
+                self.toasts.push(Toast::info("笔记已保存"));
+            }
+        }
+        if close {
+            self.mod_detail = None;
+        }
+        // 从被依赖列表跳转到另一个模组
+        if let Some(unique_id) = open_detail {
+            self.open_mod_detail(unique_id);
+        }
+    }
+
+    /// 移除模组会破坏依赖时的确认窗口
+    fn ui_pending_removal(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending_removal else {
+            return;
+        };
+        let mut confirm = false;
+        let mut cancel = false;
+        egui::Window::new("依赖警告")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "从{}中移除{}后, 以下模组将缺少必需的依赖而无法加载:",
+                    pending.profile_name, pending.mod_info.manifest_info.Name
+                ));
+                for m in &pending.broken {
+                    ui.label(format!("• {}", m.manifest_info.Name));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("仍然移除").clicked() {
+                        confirm = true;
+                    }
+                    if ui.button("取消").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if confirm {
+            let pending = self.pending_removal.take().unwrap();
+            let result = self
+                .manager
+                .remove_mod_from_profile(pending.mod_info, &pending.profile_name);
+            self.report(result);
+        }
+        if cancel {
+            self.pending_removal = None;
+        }
+    }
+
+    /// 启动前检查绑定的存档, 缺少模组时先显示警告
+    fn launch(&mut self, profile_name: String) {
+        let result = self.manager.save_warnings(&profile_name);
+        let Some(warnings) = self.report(result) else {
+            return;
+        };
+        if warnings.is_empty() {
+            self.start_game(&profile_name);
+        } else {
+            self.pending_launch = Some(PendingLaunch {
+                profile_name,
+                warnings,
+            });
+        }
+    }
+
+    /// 启动游戏, 启动前发现的问题以提示显示
+    fn start_game(&mut self, profile_name: &str) {
+        let result = self.manager.launch_stardew_valley(profile_name);
+        if let Some(report) = self.report(result) {
+            self.toasts
+                .extend(report.warnings.into_iter().map(Toast::error));
+            self.game_session = Some(report.session);
+        }
+    }
+
+    /// 存档警告窗口
+    fn ui_pending_launch(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending_launch else {
+            return;
+        };
+        let mut confirm = false;
+        let mut cancel = false;
+        egui::Window::new("存档警告")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "以下绑定到{}的存档上次游玩时使用的模组不在配置中, 继续游玩可能损坏存档:",
+                    pending.profile_name
+                ));
+                for warning in &pending.warnings {
+                    ui.label(format!(
+                        "• {}: {}",
+                        warning.folder_name,
+                        warning.missing_mods.join(", ")
+                    ));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("仍然启动").clicked() {
+                        confirm = true;
+                    }
+                    if ui.button("取消").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if confirm {
+            let pending = self.pending_launch.take().unwrap();
+            self.start_game(&pending.profile_name);
+        }
+        if cancel {
+            self.pending_launch = None;
+        }
+    }
+
+    /// 重新读取存档和它们的绑定
+    fn refresh_saves(&mut self) {
+        let (saves, mut warnings) = self.manager.discover_saves();
+        self.saves = saves;
+        let result = self.manager.get_save_records();
+        self.save_records = self.report(result).unwrap_or_default();
+        self.isolated_saves = Vec::new();
+        for profile in self.manager.get_all_profiles() {
+            if profile.saves_path.is_some() {
+                let (saves, problems) = self.manager.discover_profile_saves(&profile.name);
+                warnings.extend(problems);
+                self.isolated_saves.push((profile.name.clone(), saves));
+            }
+        }
+        self.toasts.extend(warnings.into_iter().map(Toast::error));
+        let mut save_backups = std::collections::HashMap::new();
+        let shared = self.saves.iter().map(|save| (None, save));
+        let isolated = self
+            .isolated_saves
+            .iter()
+            .flat_map(|(profile_name, saves)| {
+                saves
+                    .iter()
+                    .map(move |save| (Some(profile_name.as_str()), save))
+            });
+        for (profile_name, save) in shared.chain(isolated) {
+            let result = self
+                .manager
+                .list_save_backups(profile_name, &save.folder_name);
+            let backups = result
+                .inspect_err(|e| self.toasts.push(Toast::error(e)))
+                .unwrap_or_default();
+            save_backups.insert(
+                (profile_name.map(str::to_string), save.folder_name.clone()),
+                backups,
+            );
+        }
+        self.save_backups = save_backups;
+    }
+
+    /// - 存档列表 组件
+    /// - 设置存档目录, 把存档绑定到配置
+    fn ui_saves(&mut self, ui: &mut egui::Ui) {
+        ui.heading("存档");
+        ui.horizontal(|ui| {
+            ui.label("存档目录:");
+            ui.text_edit_singleline(&mut self.saves_path_input);
+            if ui.button("保存路径").clicked() {
+                self.set_saves_path();
+            }
+            if ui.button("刷新").clicked() {
+                self.refresh_saves();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("启动前备份绑定的存档, 保留最近");
+            ui.add(egui::DragValue::new(&mut self.backup_retention.keep_last).range(1..=100));
+            ui.label("个, 以及最近");
+            ui.add(egui::DragValue::new(&mut self.backup_retention.keep_daily).range(0..=365));
+            ui.label("天每天一个");
+            if ui.button("保存策略").clicked() {
+                self.set_backup_retention();
+            }
+        });
+        // 游戏运行时不能备份或恢复
+        let game_running = self.game_session.is_some();
+        let mut backup: Option<(Option<String>, String)> = None;
+        let mut restore: Option<SaveBackup> = None;
+        let profile_names: Vec<String> = self
+            .manager
+            .get_all_profiles()
+            .iter()
+            .map(|p| p.name.clone())
+            .collect();
+        let mut bind: Option<(String, Option<String>)> = None;
+        for save in &self.saves {
+            let record = self.save_records.get(&save.folder_name);
+            let bound = record.and_then(|r| r.profile_name.clone());
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} ({}农场) {}",
+                    save.farmer_name,
+                    save.farm_name,
+                    save.date()
+                ));
+                ui.weak(&save.folder_name);
+                let mut selected = bound.clone();
+                egui::ComboBox::from_id_salt(("save_profile", &save.folder_name))
+                    .selected_text(selected.as_deref().unwrap_or("未绑定"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut selected, None, "未绑定");
+                        for name in &profile_names {
+                            ui.selectable_value(&mut selected, Some(name.clone()), name);
+                        }
+                    });
+                if selected != bound {
+                    bind = Some((save.folder_name.clone(), selected));
+                }
+                if let Some(played_at) = record.and_then(|r| r.last_played_at.as_ref()) {
+                    ui.weak(format!("上次游玩: {}", played_at));
+                }
+                if ui
+                    .add_enabled(!game_running, egui::Button::new("备份"))
+                    .clicked()
+                {
+                    backup = Some((None, save.folder_name.clone()));
+                }
+            });
+            let key = (None, save.folder_name.clone());
+            let backups = self.save_backups.get(&key).map_or(&[][..], Vec::as_slice);
+            if let Some(b) = ui_backup_list(ui, ("save_backups", &key), backups, !game_running) {
+                restore = Some(b);
+            }
+        }
+        // 配置独立的存档目录中的存档, 备份单独存放, 恢复到该目录
+        for (profile_name, saves) in &self.isolated_saves {
+            if saves.is_empty() {
+                continue;
+            }
+            ui.label(format!("配置{}的独立存档", profile_name));
+            for save in saves {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} ({}农场) {}",
+                        save.farmer_name,
+                        save.farm_name,
+                        save.date()
+                    ));
+                    ui.weak(&save.folder_name);
+                    if ui
+                        .add_enabled(!game_running, egui::Button::new("备份"))
+                        .clicked()
+                    {
+                        backup = Some((Some(profile_name.clone()), save.folder_name.clone()));
+                    }
+                });
+                let key = (Some(profile_name.clone()), save.folder_name.clone());
+                let backups = self.save_backups.get(&key).map_or(&[][..], Vec::as_slice);
+                if let Some(b) = ui_backup_list(ui, ("save_backups", &key), backups, !game_running)
+                {
+                    restore = Some(b);
+                }
+            }
+        }
+        if let Some((profile_name, folder_name)) = backup {
+            let result = self
+                .manager
+                .backup_save(profile_name.as_deref(), &folder_name);
+            if self.report(result).is_some() {
+                self.toasts
+                    .push(Toast::info(format!("已备份{}", folder_name)));
+            }
+            self.refresh_saves();
+        }
+        if let Some(b) = restore {
+            let result = self.manager.restore_save_backup(&b);
+            if self.report(result).is_some() {
+                self.toasts.push(Toast::info(format!(
+                    "已恢复{}, 恢复前的存档也已备份",
+                    b.folder_name
+                )));
+            }
+            self.refresh_saves();
+        }
+        if let Some((folder_name, profile_name)) = bind {
+            let result = self
+                .manager
+                .bind_save_to_profile(&folder_name, profile_name.as_deref());
+            self.report(result);
+            self.refresh_saves();
+        }
+    }
+
+    /// 保存备份的保留策略到配置文件
+    fn set_backup_retention(&mut self) {
+        let retention = self.backup_retention;
+        let config_path = self.data_dir.join("setting.toml");
+        let result = crate::config::AppConfig::load_from_file(&config_path).and_then(|mut cfg| {
+            cfg.backup_retention = retention;
+            cfg.save_to_file(&config_path)
+        });
+        if self.report(result).is_some() {
+            self.manager.set_backup_retention(retention);
+            self.toasts
+                .push(Toast::info("保留策略已保存, 下次备份时生效"));
+        }
+    }
+
+    /// 保存存档目录到配置文件, 并重新读取存档
+    fn set_saves_path(&mut self) {
+        let saves_path = self.saves_path_input.trim().to_string();
+        let config_path = self.data_dir.join("setting.toml");
+        let result = crate::config::AppConfig::load_from_file(&config_path).and_then(|mut cfg| {
+            cfg.saves_path = Some(saves_path.clone());
+            cfg.save_to_file(&config_path)
+        });
+        if self.report(result).is_some() {
+            self.manager.set_saves_path(PathBuf::from(saves_path));
+            self.refresh_saves();
+        }
+    }
+
+    /// 模组库检查窗口
+    /// - 列出文件夹已被删除的模组和受影响的配置
+    /// - 提供一键清理
+    fn ui_library_health(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.library_health else {
+            return;
+        };
+        let mut close = false;
+        let mut purge = false;
+        egui::Window::new("模组库检查")
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if report.is_healthy() {
+                    ui.label("所有已注册模组的文件夹都存在.");
+                } else {
+                    ui.label(format!(
+                        "{}个模组的文件夹已不存在:",
+                        report.orphaned_mods.len()
+                    ));
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for orphan in &report.orphaned_mods {
+                                ui.horizontal(|ui| {
+                                    ui.label(&orphan.mod_info.manifest_info.Name);
+                                    ui.label(orphan.mod_info.path.display().to_string());
+                                    if orphan.profiles.is_empty() {
+                                        ui.label("未被任何配置使用");
+                                    } else {
+                                        ui.label(format!(
+                                            "受影响的配置: {}",
+                                            orphan.profiles.join(", ")
+                                        ));
+                                    }
+                                });
+                            }
+                        });
+                }
+                ui.horizontal(|ui| {
+                    if !report.is_healthy() && ui.button("清理记录和失效链接").highlight().clicked()
+                    {
+                        purge = true;
+                    }
+                    if ui.button("关闭").highlight().clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if purge {
+            if let Some(report) = self.library_health.take() {
+                for orphan in &report.orphaned_mods {
+                    self.selected_mods
+                        .remove(&orphan.mod_info.manifest_info.UniqueId);
+                }
+                let result = self.manager.purge_orphaned_mods(&report);
+                if let Some(purged) = self.report(result) {
+                    self.toasts
+                        .push(Toast::info(format!("已清理{}个模组", purged)));
+                }
+            }
+        } else if close {
+            self.library_health = None;
+        }
+    }
+
+    /// 配置检查窗口
+    /// - 列出配置目录与数据库记录的差异
+    /// - 修复后显示无法自动修复的问题
+    fn ui_profile_integrity(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.profile_integrity else {
+            return;
+        };
+        let mut close = false;
+        let mut repair = false;
+        egui::Window::new(format!("检查配置: {}", report.profile_name))
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if report.is_consistent() {
+                    ui.label("配置目录与数据库记录一致.");
+                } else {
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for issue in &report.issues {
+                                ui.label(issue.to_string());
+                            }
+                        });
+                }
+                ui.horizontal(|ui| {
+                    if !report.is_consistent() && ui.button("修复").highlight().clicked() {
+                        repair = true;
+                    }
+                    if ui.button("关闭").highlight().clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if repair {
+            let profile_name = report.profile_name.clone();
+            let result = self.manager.repair_profile(&profile_name);
+            if let Some(report) = self.report(result) {
+                self.profile_integrity = Some(report);
+            }
+        } else if close {
+            self.profile_integrity = None;
+        }
+    }
+
     /// profile列表 组件
     /// Todo: 删除操作后续改为先收集删除名单, 再统一删除
     fn ui_profile_list(&mut self, ui: &mut egui::Ui) {
         ui.heading("所有配置");
 
         let mut profiles_to_delete = Vec::new();
+        let mut profile_to_export = None;
         for profile in self.manager.get_all_profiles() {
             ui.horizontal(|ui| {
                 let selected = self.selected_profile.as_deref() == Some(&profile.name);
@@ -214,21 +1725,136 @@ impl StardewModsManagerApp {
                 if ui.button("删除配置").highlight().clicked() {
                     profiles_to_delete.push(profile.name.clone());
                 }
+                if ui.button("导出").clicked() {
+                    profile_to_export = Some(profile.name.clone());
+                }
                 ui.label(format!("信息: {}", profile.description));
             });
         }
 
+        if let Some(profile_name) = profile_to_export
+            && let Some(path) = rfd::FileDialog::new()
+                .set_file_name(format!("{}.json", profile_name))
+                .add_filter("json", &["json"])
+                .save_file()
+        {
+            let result = self.manager.export_profile(&profile_name, &path);
+            if self.report(result).is_some() {
+                self.toasts
+                    .push(Toast::info(format!("已导出到{}", path.display())));
+            }
+        }
+
         for profile_name in profiles_to_delete {
-            match self.manager.remove_profile(&profile_name) {
-                Ok(n) => {
-                    if n == 0 {
-                        self.selected_profile = None;
-                        self.selected_mods.clear();
-                    }
+            let result = self.manager.remove_profile(&profile_name);
+            if let Some(n) = self.report(result)
+                && n == 0
+            {
+                self.selected_profile = None;
+                self.selected_mods.clear();
+            }
+        }
+    }
+
+    /// 显示模组库的位置, 可以把模组迁移到游戏目录以外的模组库
+    fn ui_mods_library(&mut self, ui: &mut egui::Ui) {
+        let game_running = self.game_session.is_some();
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "模组库: {}",
+                self.manager.get_active_installation().mods_folder_path
+            ));
+            if ui
+                .add_enabled(!game_running, egui::Button::new("迁移模组库"))
+                .clicked()
+                && let Some(path) = rfd::FileDialog::new().pick_folder()
+            {
+                let result = self.manager.migrate_library(&path);
+                if let Some(moved) = self.report(result) {
+                    self.toasts.push(Toast::info(format!(
+                        "已将{}个模组移动到{}",
+                        moved,
+                        path.display()
+                    )));
+                }
+                // 监视的还是之前的文件夹
+                if self.watch_mods_folder {
+                    self.set_watch_mods_folder(true);
+                }
+            }
+        });
+    }
+
+    /// 显示当前安装的配置目录, 更改时移动已有的配置
+    fn ui_profiles_root(&mut self, ui: &mut egui::Ui) {
+        let game_running = self.game_session.is_some();
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "配置目录: {}",
+                self.manager.get_profiles_root().display()
+            ));
+            if ui
+                .add_enabled(!game_running, egui::Button::new("更改配置目录"))
+                .clicked()
+                && let Some(path) = rfd::FileDialog::new().pick_folder()
+            {
+                let result = self.manager.set_profiles_root(&path);
+                if let Some(moved) = self.report(result) {
+                    self.toasts.push(Toast::info(format!(
+                        "已将{}个配置移动到{}",
+                        moved,
+                        path.display()
+                    )));
                 }
-                Err(e) => {
-                    eprintln!("删除失败: {}", e);
+            }
+        });
+    }
+
+    /// 配置使用共享存档还是独立的存档目录, 游戏运行时不能修改
+    fn ui_profile_saves_path(&mut self, ui: &mut egui::Ui, profile_name: &str) {
+        let current = self.manager.profile_saves_path(profile_name);
+        let game_running = self.game_session.is_some();
+        let mut new_path = None;
+        ui.horizontal(|ui| {
+            match &current {
+                Some(path) => ui.label(format!("独立存档: {}", path.display())),
+                None => ui.label("使用共享存档"),
+            };
+            ui.add_enabled_ui(!game_running, |ui| {
+                if current.is_none() && ui.button("使用独立存档").clicked() {
+                    new_path = Some(Some(self.manager.default_profile_saves_path(profile_name)));
+                }
+                if ui.button("选择存档目录").clicked()
+                    && let Some(path) = rfd::FileDialog::new().pick_folder()
+                {
+                    new_path = Some(Some(path));
                 }
+                if current.is_some() && ui.button("改回共享存档").clicked() {
+                    new_path = Some(None);
+                }
+            });
+        });
+        if let Some(path) = new_path {
+            let result = self.manager.set_profile_saves_path(profile_name, path);
+            self.report(result);
+        }
+    }
+
+    /// 选择路径后导出依赖图, 扩展名为json时导出json, 否则导出Graphviz DOT
+    /// # 参数
+    /// - `profile_name`: 只导出该配置中的模组; 为None时导出整个模组库
+    fn export_dependency_graph(&mut self, profile_name: Option<&str>) {
+        let file_name = format!("{}.dot", profile_name.unwrap_or("mods"));
+        if let Some(path) = rfd::FileDialog::new()
+            .set_file_name(file_name)
+            .add_filter("Graphviz", &["dot", "gv"])
+            .add_filter("json", &["json"])
+            .save_file()
+        {
+            let result = self.manager.export_dependency_graph(profile_name, &path);
+            if self.report(result).is_some() {
+                self.toasts
+                    .push(Toast::info(format!("已导出到{}", path.display())));
             }
         }
     }
@@ -244,10 +1870,13 @@ impl StardewModsManagerApp {
                 .highlight();
             if ui.button("创建").highlight().clicked() {
                 if !self.new_profile_name.trim().is_empty() {
-                    self.manager
+                    let result = self
+                        .manager
                         .create_empty_profile(&self.new_profile_name, &self.new_profile_desc);
-                    self.new_profile_name.clear();
-                    self.new_profile_desc.clear();
+                    if self.report(result).is_some() {
+                        self.new_profile_name.clear();
+                        self.new_profile_desc.clear();
+                    }
                 }
             }
         });
@@ -256,18 +1885,103 @@ impl StardewModsManagerApp {
     /// - 显示选中的配置下的模组
     /// - 提供移除按钮
     fn ui_mods_in_profile(&mut self, ui: &mut egui::Ui) {
-        if let Some(profile_name) = &self.selected_profile {
-            ui.label(format!("{}的模组", profile_name));
-            let mods = self.manager.get_mods_from_profile(profile_name);
+        if let Some(profile_name) = self.selected_profile.clone() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}的模组", profile_name));
+                if ui.button("检查配置").highlight().clicked() {
+                    let result = self.manager.verify_profile(&profile_name);
+                    self.profile_integrity = self.report(result);
+                }
+                if ui.button("导出依赖图").clicked() {
+                    self.export_dependency_graph(Some(&profile_name));
+                }
+                let is_default = self
+                    .manager
+                    .get_active_installation()
+                    .default_profile
+                    .as_deref()
+                    == Some(profile_name.as_str());
+                let (text, default_profile) = if is_default {
+                    ("取消默认", None)
+                } else {
+                    ("设为游戏默认配置", Some(profile_name.as_str()))
+                };
+                if ui
+                    .add_enabled(self.game_session.is_none(), egui::Button::new(text))
+                    .on_hover_text("不通过管理器启动游戏时加载默认配置的模组")
+                    .clicked()
+                {
+                    let result = self.manager.set_default_profile(default_profile);
+                    self.report(result);
+                }
+            });
+            self.ui_profile_saves_path(ui, &profile_name);
+            // 复制一份, 点击移除时需要修改manager
+            let mods = self.manager.get_mods_from_profile(&profile_name).to_vec();
             for modinfo in &mods {
                 ui.horizontal(|ui| {
                     ui.label(&modinfo.manifest_info.Name);
+                    if ui.button("设置").clicked() {
+                        self.open_config_editor(
+                            &modinfo.manifest_info.UniqueId,
+                            Some(&profile_name),
+                        );
+                    }
                     if ui.button("从配置中移除").highlight().clicked() {
-                        self.manager
-                            .remove_mod_from_profile(modinfo.clone(), profile_name);
+                        let broken = self.manager.broken_by_profile_removal(
+                            &profile_name,
+                            &modinfo.manifest_info.UniqueId,
+                        );
+                        if broken.is_empty() {
+                            let result = self
+                                .manager
+                                .remove_mod_from_profile(modinfo.clone(), &profile_name);
+                            self.report(result);
+                        } else {
+                            self.pending_removal = Some(PendingRemoval {
+                                profile_name: profile_name.clone(),
+                                mod_info: modinfo.clone(),
+                                broken,
+                            });
+                        }
                     }
                 });
             }
+            // 只在展开时计算
+            ui.collapsing("加载顺序预览", |ui| {
+                let Some(load_order) = self.report(self.manager.load_order(&profile_name)) else {
+                    return;
+                };
+                for (i, modinfo) in load_order.order.iter().enumerate() {
+                    ui.label(format!(
+                        "{}. {} ({})",
+                        i + 1,
+                        modinfo.manifest_info.Name,
+                        modinfo.manifest_info.UniqueId
+                    ));
+                }
+                let cycles = load_order.cycles();
+                if !cycles.is_empty() {
+                    ui.separator();
+                    ui.label("循环依赖:");
+                    for cycle in cycles {
+                        ui.colored_label(egui::Color32::from_rgb(200, 40, 40), cycle.join(" => "));
+                    }
+                }
+                if !load_order.failures.is_empty() {
+                    ui.separator();
+                    ui.label("无法加载:");
+                    for failure in &load_order.failures {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(200, 40, 40),
+                            format!(
+                                "✘ {}: {}",
+                                failure.mod_info.manifest_info.Name, failure.reason
+                            ),
+                        );
+                    }
+                }
+            });
         }
     }
 }
@@ -276,6 +1990,9 @@ impl eframe::App for StardewModsManagerApp {
     /// Todo: ui实现组件化
     /// Todo: 模组总览, profile管理 作为两个页面展示(通过按钮调整), 1)减轻静止时内存占用; 2)更清晰的展示
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_mods_watcher(ctx);
+        self.poll_game_session(ctx);
+
         // 默认选中第一个profile
         if self.selected_profile.is_none() {
             let profiles = self.manager.get_all_profiles();
@@ -311,13 +2028,25 @@ impl eframe::App for StardewModsManagerApp {
                             }
                             if ui.button("确认重置").highlight().clicked() {
                                 // 执行真正的重置操作
-                                self.manager.reset();
+                                let result = self.manager.reset();
+                                self.report(result);
+                                self.mods_watcher = None;
+                                self.watch_mods_folder = false;
                                 self.is_beginner = true;
                                 self.show_reset_confirmation = false;
                             }
                         });
                     });
             }
+            if !self.is_beginner {
+                self.ui_installations(ui);
+            }
+            self.ui_mod_detail(ctx);
+            self.ui_config_editor(ctx);
+            self.ui_pending_removal(ctx);
+            self.ui_pending_launch(ctx);
+            self.ui_library_health(ctx);
+            self.ui_profile_integrity(ctx);
             ui.separator();
             self.ui_mods_list(ui);
             ui.separator();
@@ -328,12 +2057,18 @@ impl eframe::App for StardewModsManagerApp {
 
             self.ui_mods_in_profile(ui);
             ui.separator();
+            self.ui_saves(ui);
+            ui.separator();
             // 选择profile启动游戏
-            if let Some(profile_name) = &self.selected_profile {
-                if ui.button("启动").highlight().clicked() {
-                    self.manager.launch_stardew_valley(profile_name);
-                }
+            // 同一时间只运行一个游戏, 模组的config.json在运行期间被替换
+            if let Some(session) = &self.game_session {
+                ui.label(format!("游戏运行中: {}", session.profile_name()));
+            } else if let Some(profile_name) = self.selected_profile.clone()
+                && ui.button("启动").highlight().clicked()
+            {
+                self.launch(profile_name);
             }
         });
+        self.ui_toasts(ctx);
     }
 }
@@ -14,18 +14,19 @@ fn setup_test_mods_dir() -> PathBuf {
 #[test]
 fn test_manager_integration_flow() {
     let mods_dir = setup_test_mods_dir();
-    let mut manager = Manager::default();
+    let mut manager = Manager::default().unwrap();
     manager.set_scanner_mods_path(mods_dir);
 
-    manager.register_all_mods();
+    manager.register_all_mods().unwrap();
 
     let profile_name = "test_profile";
     let description = "测试用配置";
-    manager.create_empty_profile(profile_name, description);
+    // 之前运行留下的同名配置会返回错误, 这里忽略
+    let _ = manager.create_empty_profile(profile_name, description);
     let profiles = manager.get_all_profiles();
     assert!(profiles.iter().any(|p| p.name == profile_name));
 
-    let all_mods = manager.get_registered_mods();
+    let all_mods = manager.get_registered_mods().to_vec();
     assert!(all_mods.len() == 3, "应有3个模组被注册");
 
     // 选中部分模组加入 profile
@@ -37,8 +38,10 @@ fn test_manager_integration_flow() {
         })
         .collect();
     assert_eq!(selected_mods.len(), 2);
-    manager.insert_mods_to_profile(selected_mods.clone(), profile_name);
-    let mods_in_profile = manager.get_mods_from_profile(profile_name);
+    manager
+        .insert_mods_to_profile(selected_mods.clone(), profile_name)
+        .unwrap();
+    let mods_in_profile = manager.get_mods_from_profile(profile_name).to_vec();
     assert_eq!(mods_in_profile.len(), 2);
 
     // 测试增删改查
@@ -47,8 +50,10 @@ fn test_manager_integration_flow() {
         .into_iter()
         .find(|m| m.manifest_info.Name == "Save Backup")
         .unwrap();
-    manager.insert_mods_to_profile(vec![save_backup_mod.clone()], profile_name);
-    let mods_in_profile = manager.get_mods_from_profile(profile_name);
+    manager
+        .insert_mods_to_profile(vec![save_backup_mod.clone()], profile_name)
+        .unwrap();
+    let mods_in_profile = manager.get_mods_from_profile(profile_name).to_vec();
     assert_eq!(mods_in_profile.len(), 3);
 
     // 移除 ConsoleCommands
@@ -57,8 +62,10 @@ fn test_manager_integration_flow() {
         .find(|m| m.manifest_info.Name == "Console Commands")
         .unwrap()
         .clone();
-    manager.remove_mod_from_profile(cc_mod, profile_name);
-    let mods_in_profile = manager.get_mods_from_profile(profile_name);
+    manager
+        .remove_mod_from_profile(cc_mod, profile_name)
+        .unwrap();
+    let mods_in_profile = manager.get_mods_from_profile(profile_name).to_vec();
     assert_eq!(mods_in_profile.len(), 2);
 
     // 获取 profile
@@ -66,7 +73,7 @@ fn test_manager_integration_flow() {
     assert!(profiles.iter().any(|p| p.name == profile_name));
 
     // 启动游戏
-    manager.launch_stardew_valley(profile_name);
+    manager.launch_stardew_valley(profile_name).unwrap();
 
     // 这里的的启动函数和删除不是顺序执行的, 导致先删除后启动, debug好久...
     // manager.remove_profile(profile_name);